@@ -1,66 +1,480 @@
-use super::voice::SynthVoice;
-use crate::core::Instrument;
-use infinitedsp_core::core::channels::Mono;
+use super::voice::{SynthVoice, VoiceBank};
+use crate::core::channel::{DEFAULT_VOICE_LIMIT, MAX_VOICES, VoiceStealing};
+use crate::core::{Effect, Instrument};
+use infinitedsp_core::core::audio_param::AudioParam;
+use infinitedsp_core::core::channels::{Mono, Stereo};
 use infinitedsp_core::core::frame_processor::FrameProcessor;
+use infinitedsp_core::core::parameter::Parameter;
+use infinitedsp_core::effects::utility::panner::StereoPanner;
 
-pub struct Channel {
+// Glide speed (1/16 semitones per tick) until a tone portamento sets one.
+const DEFAULT_GLIDE_SPEED: u8 = 0x20;
+// Peak level below which a released voice counts as finished.
+const SILENCE_THRESHOLD: f32 = 1.0e-4;
+// Voices are checked for silence every this many frames of the channel's output rather than
+// once per block, so when a voice stops running does not depend on the device's block size.
+const SILENCE_CHECK_FRAMES: usize = 64;
+
+struct PooledVoice {
     voice: SynthVoice,
+    // Note counter value when the voice was last triggered
+    started: u64,
+    // Peak level over the last silence check interval, and over the running one
+    level: f32,
+    peak: f32,
+    running: bool,
+}
+
+impl PooledVoice {
+    fn new() -> Self {
+        Self {
+            voice: SynthVoice::new(),
+            started: 0,
+            level: 0.0,
+            peak: 0.0,
+            running: false,
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        !self.running
+    }
+
+    fn check_silence(&mut self) {
+        if self.running {
+            self.level = self.peak;
+            self.peak = 0.0;
+            self.running = self.voice.is_gate_open() || self.level >= SILENCE_THRESHOLD;
+        }
+    }
+}
+
+pub struct Channel {
+    // The current voice plays the channel's note and follows its effects;
+    // the others only finish their release.
+    voices: Vec<PooledVoice>,
+    // Voices in use, the first `voice_limit` of the preallocated ones
+    voice_limit: usize,
+    current: usize,
+    note_counter: u64,
+    // Frames rendered since the last silence check
+    since_silence_check: usize,
+    voice_stealing: VoiceStealing,
+    sample_rate: f32,
+    voice_buffer: Vec<f32>,
     mix_buffer: Vec<f32>,
-    current_instrument: Instrument,
+    stereo_buffer: Vec<f32>,
+    panner: StereoPanner,
+    pan: Parameter,
+    // Pan from the channel settings, and from a pan effect while playing
+    default_pan: f32,
+    pan_override: Option<f32>,
     pub last_key: u8,
+
+    // Pitch in (fractional) MIDI note units, moved by slides and portamento.
+    pitch: f32,
+    effect: Option<Effect>,
+    porta_target: f32,
+    porta_speed: u8,
+    // Glide voice mode: slide towards porta_target on every tick
+    gliding: bool,
+    vibrato_speed: u8,
+    vibrato_depth: u8,
+    vibrato_phase: f32,
 }
 
 impl Channel {
     pub fn new(sample_rate: f32) -> Self {
+        let voices = (0..MAX_VOICES).map(|_| PooledVoice::new()).collect();
+
+        let pan = Parameter::new(0.0);
+        let mut panner = StereoPanner::new(AudioParam::Linked(pan.clone()));
+        FrameProcessor::<Stereo>::set_sample_rate(&mut panner, sample_rate);
+
         Self {
-            voice: SynthVoice::new(sample_rate),
+            voices,
+            voice_limit: DEFAULT_VOICE_LIMIT,
+            current: 0,
+            note_counter: 0,
+            since_silence_check: 0,
+            voice_stealing: VoiceStealing::default(),
+            sample_rate,
+            voice_buffer: Vec::with_capacity(1024),
             mix_buffer: Vec::with_capacity(1024),
-            current_instrument: Instrument::default(),
+            stereo_buffer: Vec::with_capacity(2048),
+            panner,
+            pan,
+            default_pan: 0.0,
+            pan_override: None,
             last_key: 0,
+            pitch: 0.0,
+            effect: None,
+            porta_target: 0.0,
+            porta_speed: 0,
+            gliding: false,
+            vibrato_speed: 0,
+            vibrato_depth: 0,
+            vibrato_phase: 0.0,
+        }
+    }
+
+    fn pitch_to_freq(pitch: f32) -> f32 {
+        440.0 * 2.0f32.powf((pitch - 69.0) / 12.0)
+    }
+
+    fn voice(&mut self) -> &mut SynthVoice {
+        &mut self.voices[self.current].voice
+    }
+
+    pub fn set_voice_limit(&mut self, limit: usize) {
+        let limit = limit.clamp(1, MAX_VOICES);
+        if limit < self.voice_limit {
+            // Keep the current voice, silence the others past the limit
+            self.voices.swap(0, self.current);
+            self.current = 0;
+            for slot in &mut self.voices[limit..self.voice_limit] {
+                slot.voice.cut();
+            }
+        }
+        self.voice_limit = limit;
+    }
+
+    // Tempo for the voices' synced LFOs.
+    pub fn set_bpm(&mut self, bpm: f32) {
+        for slot in &mut self.voices {
+            slot.voice.set_bpm(bpm);
+        }
+    }
+
+    pub fn set_voice_stealing(&mut self, stealing: VoiceStealing) {
+        self.voice_stealing = stealing;
+    }
+
+    // -1.0 = left, 0.0 = center, 1.0 = right
+    pub fn set_default_pan(&mut self, pan: f32) {
+        self.default_pan = pan;
+        self.update_pan();
+    }
+
+    fn update_pan(&mut self) {
+        let pan = self.pan_override.unwrap_or(self.default_pan);
+        self.pan.set(pan.clamp(-1.0, 1.0));
+    }
+
+    // Picks an idle voice, or steals one when all of them are still sounding.
+    fn allocate_voice(&self) -> usize {
+        let voices = &self.voices[..self.voice_limit];
+        if let Some(idx) = voices.iter().position(PooledVoice::is_idle) {
+            return idx;
+        }
+        let voices = voices.iter().enumerate();
+        let stolen = match self.voice_stealing {
+            VoiceStealing::Oldest => voices.min_by_key(|(_, v)| v.started),
+            VoiceStealing::Quietest => voices.min_by(|(_, a), (_, b)| a.level.total_cmp(&b.level)),
+        };
+        stolen.map_or(0, |(idx, _)| idx)
+    }
+
+    // Starts the note on a fresh voice while the previous one keeps its release tail.
+    pub fn trigger_poly_note(
+        &mut self,
+        key: u8,
+        velocity: f32,
+        instrument_idx: usize,
+        instrument: &Instrument,
+        bank: &mut VoiceBank,
+    ) {
+        self.voice().release();
+        let idx = self.allocate_voice();
+        if idx != self.current {
+            // A stolen voice would otherwise fade in from where it was
+            self.voices[idx].voice.cut();
+        }
+        self.current = idx;
+        self.trigger_note(key, velocity, instrument_idx, instrument, bank);
+    }
+
+    pub fn trigger_note(
+        &mut self,
+        key: u8,
+        velocity: f32,
+        instrument_idx: usize,
+        instrument: &Instrument,
+        bank: &mut VoiceBank,
+    ) {
+        self.pitch = key as f32;
+        // A 3xx on a newly started note has nothing to slide to
+        self.porta_target = self.pitch;
+        self.vibrato_phase = 0.0;
+        self.gliding = false;
+        self.note_counter += 1;
+
+        let slot = &mut self.voices[self.current];
+        slot.started = self.note_counter;
+        // Counts as sounding until its first silence check, even if released right away
+        slot.level = 1.0;
+        slot.peak = 1.0;
+        slot.running = true;
+        slot.voice.release();
+        slot.voice.update_params(
+            Self::pitch_to_freq(self.pitch),
+            velocity,
+            instrument_idx,
+            instrument,
+            bank,
+        );
+        slot.voice.trigger();
+    }
+
+    pub fn legato_note(
+        &mut self,
+        key: u8,
+        velocity: f32,
+        instrument_idx: usize,
+        instrument: &Instrument,
+        bank: &mut VoiceBank,
+    ) {
+        self.pitch = key as f32;
+        self.porta_target = self.pitch;
+        self.gliding = false;
+        self.voices[self.current].voice.update_params(
+            Self::pitch_to_freq(self.pitch),
+            velocity,
+            instrument_idx,
+            instrument,
+            bank,
+        );
+    }
+
+    // Like legato_note, but the pitch slides to `key` over the following ticks.
+    pub fn glide_to(
+        &mut self,
+        key: u8,
+        velocity: f32,
+        instrument_idx: usize,
+        instrument: &Instrument,
+        bank: &mut VoiceBank,
+    ) {
+        self.porta_target = key as f32;
+        self.gliding = true;
+        self.voices[self.current].voice.update_params(
+            Self::pitch_to_freq(self.pitch),
+            velocity,
+            instrument_idx,
+            instrument,
+            bank,
+        );
+    }
+
+    // Tone portamento: keep the voice running and glide towards `key`.
+    pub fn portamento_to(&mut self, key: u8) {
+        self.porta_target = key as f32;
+    }
+
+    fn slide_to_target(&mut self, speed: u8) {
+        let step = speed as f32 / 16.0;
+        if self.pitch < self.porta_target {
+            self.pitch = (self.pitch + step).min(self.porta_target);
+        } else {
+            self.pitch = (self.pitch - step).max(self.porta_target);
         }
     }
 
-    pub fn trigger_note(&mut self, freq: f32, instrument: Option<&Instrument>) {
-        if let Some(inst) = instrument {
-            self.current_instrument = inst.clone();
+    // Called on the first tick of a row, after the row's note has been handled.
+    pub fn set_effect(&mut self, effect: Option<Effect>) {
+        match effect {
+            Some(Effect::SetPan(pan)) => {
+                // 00 = left, 80 = center, FF = right
+                self.pan_override = Some((pan as f32 - 128.0) / 127.0);
+                self.update_pan();
+            }
+            Some(Effect::TonePortamento(speed)) if speed > 0 => self.porta_speed = speed,
+            Some(Effect::Vibrato { speed, depth }) => {
+                if speed > 0 {
+                    self.vibrato_speed = speed;
+                }
+                if depth > 0 {
+                    self.vibrato_depth = depth;
+                }
+            }
+            _ => {}
+        }
+        self.effect = effect;
+        self.apply_pitch(0.0);
+
+        if effect == Some(Effect::NoteCut(0)) {
+            self.cut_note();
         }
-        self.voice.release();
-        self.voice
-            .update_params(freq, Some(&self.current_instrument));
-        self.voice.trigger();
     }
 
-    pub fn legato_note(&mut self, freq: f32, instrument: Option<&Instrument>) {
-        if let Some(inst) = instrument {
-            self.current_instrument = inst.clone();
+    // ECx: the note ends like a note cut cell, so the next note starts afresh.
+    fn cut_note(&mut self) {
+        self.voice().cut();
+        self.last_key = 0;
+        self.gliding = false;
+    }
+
+    // Called on every tick of a row except the first.
+    pub fn tick_effect(&mut self, tick: usize) {
+        // Tone portamento takes over the slide while it runs
+        let glided = self.gliding && !matches!(self.effect, Some(Effect::TonePortamento(_)));
+        if glided {
+            let speed = if self.porta_speed > 0 {
+                self.porta_speed
+            } else {
+                DEFAULT_GLIDE_SPEED
+            };
+            self.slide_to_target(speed);
+            self.gliding = self.pitch != self.porta_target;
         }
-        self.voice
-            .update_params(freq, Some(&self.current_instrument));
+
+        let offset = match self.effect {
+            Some(Effect::Retrigger(interval)) => {
+                if interval > 0 && tick.is_multiple_of(interval as usize) {
+                    self.voice().release();
+                    self.voice().trigger();
+                }
+                0.0
+            }
+            Some(Effect::NoteCut(cut_tick)) => {
+                if tick == cut_tick as usize {
+                    self.cut_note();
+                }
+                0.0
+            }
+            Some(Effect::Arpeggio { x, y }) => match tick % 3 {
+                1 => x as f32,
+                2 => y as f32,
+                _ => 0.0,
+            },
+            Some(Effect::SlideUp(speed)) => {
+                self.pitch = (self.pitch + speed as f32 / 16.0).min(127.0);
+                0.0
+            }
+            Some(Effect::SlideDown(speed)) => {
+                self.pitch = (self.pitch - speed as f32 / 16.0).max(0.0);
+                0.0
+            }
+            Some(Effect::TonePortamento(_)) => {
+                self.slide_to_target(self.porta_speed);
+                0.0
+            }
+            Some(Effect::Vibrato { .. }) => {
+                self.vibrato_phase =
+                    (self.vibrato_phase + self.vibrato_speed as f32 / 64.0).fract();
+                (self.vibrato_phase * std::f32::consts::TAU).sin() * self.vibrato_depth as f32 / 8.0
+            }
+            _ if glided => 0.0,
+            _ => return,
+        };
+        self.apply_pitch(offset);
+    }
+
+    fn apply_pitch(&mut self, offset: f32) {
+        let freq = Self::pitch_to_freq(self.pitch + offset);
+        self.voice().set_pitch(freq);
     }
 
     pub fn release(&mut self) {
-        self.voice.release();
+        self.voice().release();
+    }
+
+    // Silences every voice of the channel, including release tails.
+    pub fn cut(&mut self) {
+        for slot in &mut self.voices {
+            slot.voice.cut();
+        }
+    }
+
+    // Gives the processors of every voice back, once the channel has been cut.
+    pub fn return_voices(&mut self, bank: &mut VoiceBank) {
+        for slot in &mut self.voices {
+            slot.voice.return_graph(bank);
+        }
     }
 
     pub fn silence(&mut self) {
-        self.voice.release();
+        for slot in &mut self.voices {
+            slot.voice.release();
+        }
         self.last_key = 0;
+        self.effect = None;
+        self.gliding = false;
+        self.pan_override = None;
+        self.update_pan();
     }
 
-    pub fn process(&mut self, buffer_len: usize, sample_index: u64) -> &[f32] {
-        if self.mix_buffer.len() < buffer_len {
-            self.mix_buffer.resize(buffer_len, 0.0);
+    // Returns `frames` interleaved stereo frames, which the insert chain may process in place.
+    pub fn process(
+        &mut self,
+        frames: usize,
+        sample_index: u64,
+        bank: &mut VoiceBank,
+    ) -> &mut [f32] {
+        if self.mix_buffer.len() < frames {
+            self.mix_buffer.resize(frames, 0.0);
+        }
+
+        if self.voice_buffer.len() < frames {
+            self.voice_buffer.resize(frames, 0.0);
         }
 
-        let slice = &mut self.mix_buffer[0..buffer_len];
+        if self.stereo_buffer.len() < frames * 2 {
+            self.stereo_buffer.resize(frames * 2, 0.0);
+        }
+
+        let slice = &mut self.mix_buffer[0..frames];
         slice.fill(0.0);
 
-        FrameProcessor::<Mono>::process(&mut self.voice, slice, sample_index);
+        let mut done = 0;
+        while done < frames {
+            let span = (SILENCE_CHECK_FRAMES - self.since_silence_check).min(frames - done);
+            let out = &mut slice[done..done + span];
+            for slot in &mut self.voices {
+                // Finished voices give their processors back and are skipped until triggered
+                if slot.is_idle() {
+                    slot.voice.return_graph(bank);
+                    continue;
+                }
+
+                let voice_slice = &mut self.voice_buffer[0..span];
+                voice_slice.fill(0.0);
+                let index = sample_index + done as u64;
+                FrameProcessor::<Mono>::process(&mut slot.voice, voice_slice, index);
 
-        slice
+                slot.peak = voice_slice
+                    .iter()
+                    .fold(slot.peak, |peak, s| peak.max(s.abs()));
+                for (out, sample) in out.iter_mut().zip(voice_slice.iter()) {
+                    *out += sample;
+                }
+            }
+
+            done += span;
+            self.since_silence_check += span;
+            if self.since_silence_check == SILENCE_CHECK_FRAMES {
+                self.since_silence_check = 0;
+                for slot in &mut self.voices {
+                    slot.check_silence();
+                }
+            }
+        }
+
+        let stereo = &mut self.stereo_buffer[0..frames * 2];
+        for (frame, &sample) in stereo.chunks_mut(2).zip(slice.iter()) {
+            frame[0] = sample;
+            frame[1] = sample;
+        }
+        FrameProcessor::<Stereo>::process(&mut self.panner, stereo, sample_index);
+
+        stereo
     }
 
     pub fn set_sample_rate(&mut self, sample_rate: f32) {
-        FrameProcessor::<Mono>::set_sample_rate(&mut self.voice, sample_rate);
+        self.sample_rate = sample_rate;
+        for slot in &mut self.voices {
+            FrameProcessor::<Mono>::set_sample_rate(&mut slot.voice, sample_rate);
+        }
+        FrameProcessor::<Stereo>::set_sample_rate(&mut self.panner, sample_rate);
     }
 }
@@ -0,0 +1,336 @@
+use super::insert::{self, InsertChain};
+use super::voice::{self, MAX_RETIRED, VoiceGraph, VoicePool};
+use crate::core::channel::MAX_VOICES;
+use crate::core::pattern::{MAX_CHANNELS, Note};
+use crate::core::{InsertConfig, ModuleConfig, NUM_INSTRUMENTS, SharedState};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, mpsc};
+
+// Capacity of the queues; commands that do not fit wait on the UI side.
+const COMMAND_QUEUE_SIZE: usize = 64;
+// Bits per field of the packed playback position.
+const POSITION_BITS: u32 = 21;
+const POSITION_MASK: u64 = (1 << POSITION_BITS) - 1;
+// Published in place of a queued pattern when there is none.
+const NO_PATTERN: usize = usize::MAX;
+// Voices per instrument on top of what the channels playing it need, for previews.
+const SPARE_VOICES: usize = 2;
+
+/// A copy of the song with the processors the engine needs for it, built on the UI thread.
+/// The engine swaps its own for them and sends the update back, holding the old ones, to be
+/// freed on the UI thread.
+pub struct SongUpdate {
+    pub state: Box<SharedState>,
+    // Per instrument, a new voice pool where the old one no longer fits the song
+    pub voices: Vec<Option<VoicePool>>,
+    // Per channel, a new insert chain where its effects or their modes changed
+    pub inserts: Vec<Option<InsertChain>>,
+    // Room for the voice graphs the engine retired, which come back in it
+    pub retired: Vec<VoiceGraph>,
+}
+
+/// Edits and transport requests from the UI to the audio thread.
+pub enum EngineCommand {
+    // Replaces the song; the engine keeps its playback position and timing
+    Song(Box<SongUpdate>),
+    Play {
+        order: usize,
+        pattern: usize,
+        row: usize,
+        stop_after: Option<usize>,
+    },
+    Stop,
+    // Moves playback to another pattern, right away or queued as the song's pattern launch says
+    SwitchPattern(usize),
+    SetTiming {
+        bpm: f32,
+        ticks_per_row: usize,
+    },
+    Preview {
+        channel: usize,
+        note: Note,
+    },
+}
+
+/// Playback position, timing and meters, written by the audio thread after every block.
+pub struct EngineStatus {
+    is_playing: AtomicBool,
+    // Song position, pattern and row packed together so they are read consistently
+    position: AtomicU64,
+    queued_pattern: AtomicUsize,
+    bpm: AtomicU32,
+    ticks_per_row: AtomicUsize,
+    master_peak: AtomicU32,
+    channel_peaks: Vec<AtomicU32>,
+}
+
+impl EngineStatus {
+    fn new() -> Self {
+        Self {
+            is_playing: AtomicBool::new(false),
+            position: AtomicU64::new(0),
+            queued_pattern: AtomicUsize::new(NO_PATTERN),
+            bpm: AtomicU32::new(0),
+            ticks_per_row: AtomicUsize::new(0),
+            master_peak: AtomicU32::new(0),
+            channel_peaks: (0..MAX_CHANNELS).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+
+    fn pack_position(order: usize, pattern: usize, row: usize) -> u64 {
+        let field = |value: usize| (value as u64).min(POSITION_MASK);
+        (field(order) << (2 * POSITION_BITS)) | (field(pattern) << POSITION_BITS) | field(row)
+    }
+
+    fn unpack_position(position: u64) -> (usize, usize, usize) {
+        (
+            (position >> (2 * POSITION_BITS)) as usize,
+            ((position >> POSITION_BITS) & POSITION_MASK) as usize,
+            (position & POSITION_MASK) as usize,
+        )
+    }
+
+    pub(super) fn publish(&self, state: &SharedState, channel_peaks: &[f32], master_peak: f32) {
+        let position = Self::pack_position(
+            state.current_order,
+            state.current_pattern,
+            state.current_row,
+        );
+        self.position.store(position, Ordering::Relaxed);
+        self.queued_pattern.store(
+            state.queued_pattern.unwrap_or(NO_PATTERN),
+            Ordering::Relaxed,
+        );
+        self.bpm.store(state.bpm.to_bits(), Ordering::Relaxed);
+        self.ticks_per_row
+            .store(state.ticks_per_row, Ordering::Relaxed);
+        for (meter, peak) in self.channel_peaks.iter().zip(channel_peaks) {
+            meter.store(peak.to_bits(), Ordering::Relaxed);
+        }
+        self.master_peak
+            .store(master_peak.to_bits(), Ordering::Relaxed);
+        self.is_playing.store(state.is_playing, Ordering::Release);
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.is_playing.load(Ordering::Acquire)
+    }
+
+    // Copies meters into the UI's state, and the position and timing while the engine plays
+    // (or has just stopped), since only then does the engine move them. The UI never sets
+    // `is_playing` itself, so it reflects commands the engine has carried out.
+    fn apply_to(&self, state: &mut SharedState) {
+        let is_playing = self.is_playing();
+        if is_playing || state.is_playing {
+            let (order, pattern, row) =
+                Self::unpack_position(self.position.load(Ordering::Relaxed));
+            if pattern < state.patterns.len() && order < state.order.len() {
+                state.current_order = order;
+                state.current_pattern = pattern;
+                state.current_row = row;
+            }
+            let queued = self.queued_pattern.load(Ordering::Relaxed);
+            state.queued_pattern = Some(queued).filter(|&pattern| pattern < state.patterns.len());
+            let bpm = f32::from_bits(self.bpm.load(Ordering::Relaxed));
+            let ticks_per_row = self.ticks_per_row.load(Ordering::Relaxed);
+            if bpm != state.bpm || ticks_per_row != state.ticks_per_row {
+                state.set_timing(bpm, ticks_per_row);
+            }
+            state.is_playing = is_playing;
+        }
+
+        state.channel_peaks.clear();
+        state.channel_peaks.extend(
+            self.channel_peaks[..state.num_channels]
+                .iter()
+                .map(|meter| f32::from_bits(meter.load(Ordering::Relaxed))),
+        );
+        state.master_peak = f32::from_bits(self.master_peak.load(Ordering::Relaxed));
+    }
+}
+
+/// The audio thread's end of the link to the UI.
+pub(super) struct EngineLink {
+    pub commands: Receiver<EngineCommand>,
+    // Replaced songs go back to the UI thread to be freed there
+    pub released: SyncSender<Box<SongUpdate>>,
+    pub status: Arc<EngineStatus>,
+}
+
+/// The UI's end of the link to the audio thread. Neither side ever blocks: the bounded queues
+/// are allocated up front, and only `try_send` / `try_recv` are used on them.
+pub struct EngineHandle {
+    commands: SyncSender<EngineCommand>,
+    released: Receiver<Box<SongUpdate>>,
+    status: Arc<EngineStatus>,
+    // Commands that did not fit into the queue yet, sent in order by `sync`
+    pending: VecDeque<EngineCommand>,
+    sample_rate: f32,
+    // Last voice pool generation handed out
+    generation: u64,
+    // What the engine's voice pool for each instrument was built for
+    sent_pools: Vec<SentPool>,
+    // The effects each of the engine's insert chains was built for
+    sent_inserts: Vec<Vec<InsertConfig>>,
+}
+
+struct SentPool {
+    modules: Vec<ModuleConfig>,
+    voices: usize,
+}
+
+// Voices that may play each instrument at once: the voice limit of every channel with notes
+// for it, plus spares for previews.
+fn voices_per_instrument(state: &SharedState) -> Vec<usize> {
+    let mut voices = vec![SPARE_VOICES; NUM_INSTRUMENTS];
+    let channels = state.channel_settings.iter().take(state.num_channels);
+    for (channel, settings) in channels.enumerate() {
+        let mut used = [false; NUM_INSTRUMENTS];
+        let notes = state
+            .patterns
+            .iter()
+            .flat_map(|pattern| &pattern.rows)
+            .filter_map(|row| row.get(channel))
+            .filter(|note| note.is_note());
+        for note in notes {
+            used[note.instrument_idx(channel) % NUM_INSTRUMENTS] = true;
+        }
+        let limit = settings.voice_limit.clamp(1, MAX_VOICES);
+        for (count, _) in voices.iter_mut().zip(used).filter(|(_, used)| *used) {
+            *count += limit;
+        }
+    }
+    voices
+}
+
+pub(super) fn link(sample_rate: f32) -> (EngineLink, EngineHandle) {
+    let (command_tx, command_rx) = mpsc::sync_channel(COMMAND_QUEUE_SIZE);
+    let (released_tx, released_rx) = mpsc::sync_channel(COMMAND_QUEUE_SIZE);
+    let status = Arc::new(EngineStatus::new());
+
+    let engine_link = EngineLink {
+        commands: command_rx,
+        released: released_tx,
+        status: status.clone(),
+    };
+    let handle = EngineHandle {
+        commands: command_tx,
+        released: released_rx,
+        status,
+        pending: VecDeque::new(),
+        sample_rate,
+        generation: 0,
+        sent_pools: (0..NUM_INSTRUMENTS)
+            .map(|_| SentPool {
+                modules: Vec::new(),
+                voices: 0,
+            })
+            .collect(),
+        sent_inserts: vec![Vec::new(); MAX_CHANNELS],
+    };
+    (engine_link, handle)
+}
+
+impl EngineHandle {
+    pub fn send(&mut self, command: EngineCommand) {
+        self.pending.push_back(command);
+        self.flush();
+    }
+
+    // Wraps a copy of the song with new voice pools for the instruments whose pools no longer
+    // fit it, because their modules changed or more channels play them, and new insert chains
+    // for the channels whose effects changed.
+    pub(super) fn song_update(&mut self, state: &SharedState) -> SongUpdate {
+        let needed = voices_per_instrument(state);
+        let mut voices = Vec::with_capacity(NUM_INSTRUMENTS);
+        for (idx, (sent, voice_count)) in self.sent_pools.iter_mut().zip(needed).enumerate() {
+            let instrument = &state.instruments[idx];
+            if sent.voices >= voice_count && voice::same_layout(&sent.modules, &instrument.modules)
+            {
+                voices.push(None);
+                continue;
+            }
+            self.generation += 1;
+            sent.modules.clone_from(&instrument.modules);
+            sent.voices = voice_count;
+            voices.push(Some(VoicePool::new(
+                idx,
+                self.generation,
+                instrument,
+                voice_count,
+                state.bpm,
+                self.sample_rate,
+            )));
+        }
+        let mut inserts = Vec::with_capacity(MAX_CHANNELS);
+        for (idx, sent) in self.sent_inserts.iter_mut().enumerate() {
+            let configs = state
+                .channel_settings
+                .get(idx)
+                .map_or(&[][..], |settings| &settings.inserts[..]);
+            if insert::same_layout(sent, configs) {
+                inserts.push(None);
+            } else {
+                *sent = configs.to_vec();
+                inserts.push(Some(InsertChain::new(self.sample_rate, configs)));
+            }
+        }
+        SongUpdate {
+            state: Box::new(state.clone()),
+            voices,
+            inserts,
+            retired: Vec::with_capacity(MAX_RETIRED),
+        }
+    }
+
+    // Sends a copy of the song after the UI edited it.
+    pub fn send_song(&mut self, state: &SharedState) {
+        let mut update = self.song_update(state);
+        // Only the latest copy matters, so one still waiting for the queue is replaced; the
+        // pools it carries are still the ones the engine should get
+        if let Some(index) = self
+            .pending
+            .iter()
+            .rposition(|command| matches!(command, EngineCommand::Song(_)))
+            && let Some(EngineCommand::Song(mut old)) = self.pending.remove(index)
+        {
+            for (pool, old_pool) in update.voices.iter_mut().zip(&mut old.voices) {
+                if pool.is_none() {
+                    *pool = old_pool.take();
+                }
+            }
+            for (chain, old_chain) in update.inserts.iter_mut().zip(&mut old.inserts) {
+                if chain.is_none() {
+                    *chain = old_chain.take();
+                }
+            }
+        }
+        self.send(EngineCommand::Song(Box::new(update)));
+    }
+
+    fn flush(&mut self) {
+        while let Some(command) = self.pending.pop_front() {
+            match self.commands.try_send(command) {
+                Ok(()) => {}
+                Err(TrySendError::Full(command)) => {
+                    self.pending.push_front(command);
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.pending.clear();
+                    break;
+                }
+            }
+        }
+    }
+
+    // Called once per UI frame: sends waiting commands, frees songs the engine replaced and
+    // copies the engine's position and meters into `state`.
+    pub fn sync(&mut self, state: &mut SharedState) {
+        self.flush();
+        while self.released.try_recv().is_ok() {}
+        self.status.apply_to(state);
+    }
+}
@@ -1,156 +1,563 @@
 use super::channel::Channel;
-use crate::core::state::PlayMode;
-use crate::core::{NUM_CHANNELS, NUM_INSTRUMENTS, ROWS_PER_PATTERN, SharedState};
-use infinitedsp_core::core::channels::Mono;
+use super::control::{self, EngineCommand, EngineHandle, EngineLink, SongUpdate};
+use super::insert::InsertChain;
+use super::mixer::{Mixer, StripLevels};
+use super::voice::VoiceBank;
+use crate::core::pattern::Note;
+use crate::core::state::{PatternLaunch, PlayMode};
+use crate::core::{
+    Effect, Instrument, MAX_CHANNELS, NOTE_CUT, NOTE_OFF, NUM_INSTRUMENTS, SharedState, VoiceMode,
+};
+use infinitedsp_core::core::channels::Stereo;
 use infinitedsp_core::core::frame_processor::FrameProcessor;
-use std::sync::{Arc, Mutex};
+use std::collections::HashSet;
+use std::sync::mpsc::TrySendError;
 
+// Frames rendered per engine call; larger device buffers are filled in several calls, so the
+// callback never has to grow a buffer.
+pub const MAX_BLOCK_FRAMES: usize = 1024;
+
+// The engine runs in the audio callback, so it never locks and never allocates for song
+// edits: it owns its copy of the song, swaps in copies from the UI together with the voice
+// pools and insert chains built for them there, and hands the old ones back to be freed.
+// Channel slots and their MAX_VOICES voices exist up front.
 pub struct TrackerEngine {
-    channels: [Channel; NUM_CHANNELS],
-    state: Arc<Mutex<SharedState>>,
-    preview_timers: [usize; NUM_CHANNELS],
+    channels: Vec<Channel>,
+    // Channels in use, the first `num_channels` of the project
+    num_channels: usize,
+    inserts: Vec<InsertChain>,
+    voice_bank: VoiceBank,
+    // Fader and send gains per channel, volume 0 when muted or not soloed; refreshed every block
+    levels: Vec<StripLevels>,
+    master_volume: f32,
+    mixer: Mixer,
+    state: Box<SharedState>,
+    link: EngineLink,
+    // A replaced song the full release queue did not take yet; no commands are handled until
+    // it is handed back, so it is never freed here
+    unreleased: Option<Box<SongUpdate>>,
+    preview_timers: Vec<usize>,
     preview_duration: usize,
+    row_tick: usize,
+    delayed_notes: Vec<Option<(usize, Note)>>,
+    pending_jump: Option<usize>,
+    pending_break: Option<usize>,
+    // Set once the row to stop after has played; playback stops when the next row would start
+    stop_pending: bool,
+    // BPM and ticks per row before effect commands changed them, restored on stop.
+    timing_before_effects: Option<(f32, usize)>,
+    // Groove offset of the playing row in samples, the start of the next row is shifted by
+    // the difference to its own
+    groove_offset: f64,
+    // Rows played so far, only tracked when playback should stop once the song loops.
+    visited_rows: Option<HashSet<(usize, usize)>>,
     was_playing: bool,
     sample_rate: f32,
 }
 
 impl TrackerEngine {
-    pub fn new(sample_rate: f32, state: Arc<Mutex<SharedState>>) -> Self {
-        let mut channels_vec = Vec::with_capacity(NUM_CHANNELS);
-        for _ in 0..NUM_CHANNELS {
-            channels_vec.push(Channel::new(sample_rate));
+    // Returns the engine, to be moved to the audio thread, and the UI's handle to control it.
+    pub fn new(sample_rate: f32, state: SharedState) -> (Self, EngineHandle) {
+        let mut channels = Vec::with_capacity(MAX_CHANNELS);
+        for _ in 0..MAX_CHANNELS {
+            channels.push(Channel::new(sample_rate));
         }
+        let (link, mut handle) = control::link(sample_rate);
+        let mut update = handle.song_update(&state);
 
-        let channels: [Channel; NUM_CHANNELS] = channels_vec
-            .try_into()
-            .map_err(|_| "Failed to init channels")
-            .unwrap();
-
-        Self {
+        let mut engine = Self {
             channels,
-            state,
-            preview_timers: [0; NUM_CHANNELS],
+            num_channels: state.num_channels,
+            inserts: (0..MAX_CHANNELS)
+                .map(|_| InsertChain::new(sample_rate, &[]))
+                .collect(),
+            voice_bank: VoiceBank::new(),
+            levels: vec![StripLevels::default(); MAX_CHANNELS],
+            master_volume: 1.0,
+            mixer: Mixer::new(sample_rate, MAX_CHANNELS),
+            state: Box::new(state),
+            link,
+            unreleased: None,
+            preview_timers: vec![0; MAX_CHANNELS],
             preview_duration: (sample_rate * 0.5) as usize,
+            row_tick: 0,
+            delayed_notes: vec![None; MAX_CHANNELS],
+            pending_jump: None,
+            pending_break: None,
+            stop_pending: false,
+            timing_before_effects: None,
+            groove_offset: 0.0,
+            visited_rows: None,
             was_playing: false,
             sample_rate,
+        };
+        engine.swap_processors(&mut update);
+        (engine, handle)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.is_playing
+    }
+
+    // Takes the voice pools and insert chains of an update, leaving the replaced ones in it.
+    fn swap_processors(&mut self, update: &mut SongUpdate) {
+        self.voice_bank.swap_pools(&mut update.voices);
+        self.voice_bank.swap_retired(&mut update.retired);
+        for (chain, new) in self.inserts.iter_mut().zip(&mut update.inserts) {
+            if let Some(new) = new {
+                std::mem::swap(chain, new);
+            }
         }
     }
 
-    fn midi_to_freq(midi: u8) -> f32 {
-        440.0 * 2.0f32.powf((midi as f32 - 69.0) / 12.0)
+    // Hands a replaced song back to the UI thread, or keeps it until the queue has room.
+    fn release(&mut self, old: Box<SongUpdate>) {
+        if let Err(TrySendError::Full(old) | TrySendError::Disconnected(old)) =
+            self.link.released.try_send(old)
+        {
+            self.unreleased = Some(old);
+        }
+    }
+
+    fn handle_command(&mut self, command: EngineCommand) {
+        match command {
+            EngineCommand::Song(mut update) => {
+                // Playback position and timing belong to the engine
+                let state = &self.state;
+                let song = &mut update.state;
+                song.current_pattern = state.current_pattern.min(song.patterns.len() - 1);
+                song.current_order = state.current_order.min(song.order.len() - 1);
+                song.current_row = state
+                    .current_row
+                    .min(song.patterns[song.current_pattern].len() - 1);
+                song.is_playing = state.is_playing;
+                song.stop_after_row = state.stop_after_row;
+                song.queued_pattern = state
+                    .queued_pattern
+                    .filter(|&pattern| pattern < song.patterns.len());
+                song.bpm = state.bpm;
+                song.ticks_per_row = state.ticks_per_row;
+                song.sample_rate = state.sample_rate;
+                song.samples_per_tick = state.samples_per_tick;
+                song.current_tick_samples = state.current_tick_samples;
+
+                std::mem::swap(&mut self.state, &mut update.state);
+                self.swap_processors(&mut update);
+                self.release(update);
+            }
+            EngineCommand::Play {
+                order,
+                pattern,
+                row,
+                stop_after,
+            } => {
+                self.state.play_at(order, pattern, row);
+                self.state.stop_after_row = stop_after;
+                // Otherwise done once playback starts
+                if self.was_playing {
+                    self.start_groove();
+                }
+            }
+            EngineCommand::Stop => {
+                self.state.is_playing = false;
+                self.state.queued_pattern = None;
+            }
+            EngineCommand::SwitchPattern(pattern) => {
+                let state = &mut *self.state;
+                if state.is_playing && pattern < state.patterns.len() {
+                    if state.pattern_launch == PatternLaunch::Immediate {
+                        state.current_pattern = pattern;
+                        state.current_row =
+                            state.current_row.min(state.patterns[pattern].len() - 1);
+                        state.queued_pattern = None;
+                    } else {
+                        state.queued_pattern = Some(pattern);
+                    }
+                }
+            }
+            EngineCommand::SetTiming { bpm, ticks_per_row } => {
+                // The user's tempo stays when playback stops
+                self.timing_before_effects = None;
+                self.state.set_timing(bpm, ticks_per_row);
+            }
+            EngineCommand::Preview { channel, note } => {
+                if channel < self.state.num_channels {
+                    if note.is_note() {
+                        let inst_idx = note.instrument_idx(channel) % NUM_INSTRUMENTS;
+                        self.channels[channel].trigger_note(
+                            note.key,
+                            note.velocity(),
+                            inst_idx,
+                            &self.state.instruments[inst_idx],
+                            &mut self.voice_bank,
+                        );
+                        self.channels[channel].last_key = note.key;
+                        self.preview_timers[channel] = self.preview_duration;
+                    } else {
+                        self.channels[channel].release();
+                        self.preview_timers[channel] = 0;
+                    }
+                }
+            }
+        }
+    }
+
+    // Stop playback instead of revisiting a row, so offline rendering ends with the song.
+    pub fn set_stop_on_loop(&mut self, stop: bool) {
+        self.visited_rows = if stop { Some(HashSet::new()) } else { None };
+    }
+
+    fn play_note(
+        channel: &mut Channel,
+        ch_idx: usize,
+        note: &Note,
+        voice_mode: VoiceMode,
+        instruments: &[Instrument],
+        voice_bank: &mut VoiceBank,
+    ) {
+        match note.key {
+            NOTE_OFF => {
+                channel.release();
+                channel.last_key = 0;
+                return;
+            }
+            NOTE_CUT => {
+                channel.cut();
+                channel.last_key = 0;
+                return;
+            }
+            _ => {}
+        }
+
+        let inst_idx = note.instrument_idx(ch_idx) % NUM_INSTRUMENTS;
+        let instrument = &instruments[inst_idx];
+        let velocity = note.velocity();
+
+        // Tone portamento always glides from the sounding note, otherwise the channel's
+        // voice mode decides.
+        let is_portamento = matches!(note.effect(), Some(Effect::TonePortamento(_)));
+        let is_sounding = channel.last_key > 0;
+
+        if is_portamento && is_sounding {
+            channel.portamento_to(note.key);
+        } else {
+            match voice_mode {
+                VoiceMode::Legato if note.key == channel.last_key => {
+                    channel.legato_note(note.key, velocity, inst_idx, instrument, voice_bank);
+                }
+                VoiceMode::Glide if is_sounding => {
+                    channel.glide_to(note.key, velocity, inst_idx, instrument, voice_bank);
+                }
+                VoiceMode::Poly => {
+                    channel.trigger_poly_note(note.key, velocity, inst_idx, instrument, voice_bank);
+                }
+                _ => channel.trigger_note(note.key, velocity, inst_idx, instrument, voice_bank),
+            }
+        }
+
+        channel.last_key = note.key;
     }
 
     fn tick(&mut self) {
-        let mut state = self.state.lock().unwrap();
+        let state = &mut *self.state;
         if !state.is_playing {
             return;
         }
 
-        let row_idx = state.current_row;
-        let pattern_idx = state.current_pattern;
+        if self.row_tick > 0 {
+            for (i, channel) in self.channels[..state.num_channels].iter_mut().enumerate() {
+                if let Some((delay, note)) = self.delayed_notes[i]
+                    && delay == self.row_tick
+                {
+                    self.delayed_notes[i] = None;
+                    let voice_mode = state.channel_settings[i].voice_mode;
+                    Self::play_note(
+                        channel,
+                        i,
+                        &note,
+                        voice_mode,
+                        &state.instruments,
+                        &mut self.voice_bank,
+                    );
+                }
+                channel.tick_effect(self.row_tick);
+            }
+        } else {
+            // Notes delayed past the end of their row are dropped
+            self.delayed_notes.fill(None);
 
-        // Ensure we have a valid pattern
-        if pattern_idx >= state.patterns.len() {
-            state.current_pattern = 0;
-        }
+            if self.stop_pending {
+                self.stop_pending = false;
+                state.is_playing = false;
+                return;
+            }
 
-        // Safety check for row index
-        let current_pattern = &state.patterns[state.current_pattern];
-        if row_idx >= current_pattern.rows.len() {
-            // If we are out of bounds, just advance to next row/pattern logic
-            // This prevents panic if loaded pattern is shorter than ROWS_PER_PATTERN
-        } else {
-            let row = current_pattern.rows[row_idx];
+            let row_idx = state.current_row;
+            let pattern_idx = state.current_pattern;
 
-            for (i, note) in row.iter().enumerate() {
-                let inst_idx = i % NUM_INSTRUMENTS;
-                let instrument = Some(&state.instruments[inst_idx]);
+            // Ensure we have a valid pattern
+            if pattern_idx >= state.patterns.len() {
+                state.current_pattern = 0;
+            }
+            if state.current_order >= state.order.len() {
+                state.current_order = 0;
+            }
+
+            if let Some(visited) = &mut self.visited_rows
+                && !visited.insert((state.current_order, row_idx))
+            {
+                state.is_playing = false;
+                return;
+            }
 
-                if note.key > 0 {
-                    let freq = Self::midi_to_freq(note.key);
+            // Safety check for row index
+            let current_pattern = &state.patterns[state.current_pattern];
+            if row_idx >= current_pattern.len() {
+                // The pattern was shortened while playing, just advance to next row/pattern logic
+            } else {
+                let row = &current_pattern.rows[row_idx];
+                let mut timing = (state.bpm, state.ticks_per_row);
 
-                    // Logic:
-                    // Channels 0-2 are Drums -> Always Retrigger
-                    // Channels 3+ are Synths -> Legato if same key
-                    let is_drum_channel = i <= 2;
+                for (i, note) in row.iter().enumerate().take(state.num_channels) {
+                    let effect = note.effect();
 
-                    if !is_drum_channel && note.key == self.channels[i].last_key {
-                        self.channels[i].legato_note(freq, instrument);
-                    } else {
-                        self.channels[i].trigger_note(freq, instrument);
+                    match effect {
+                        Some(Effect::PositionJump(pos)) => self.pending_jump = Some(pos as usize),
+                        Some(Effect::PatternBreak(row)) => self.pending_break = Some(row as usize),
+                        Some(Effect::SetTicksPerRow(ticks)) => timing.1 = ticks as usize,
+                        Some(Effect::SetBpm(bpm)) => timing.0 = bpm as f32,
+                        _ => {}
+                    }
+
+                    // Empty cells keep the channel playing; note-off / note-cut end it.
+                    if note.key > 0 {
+                        if let Some(Effect::NoteDelay(delay)) = effect
+                            && delay > 0
+                        {
+                            self.delayed_notes[i] = Some((delay as usize, *note));
+                        } else {
+                            Self::play_note(
+                                &mut self.channels[i],
+                                i,
+                                note,
+                                state.channel_settings[i].voice_mode,
+                                &state.instruments,
+                                &mut self.voice_bank,
+                            );
+                        }
                     }
 
-                    self.channels[i].last_key = note.key;
-                } else {
-                    // Empty row behavior:
-                    // Empty row triggers Note Off (Release) to allow for staccato.
-                    // Legato is achieved by placing consecutive notes without gaps.
-                    self.channels[i].release();
-                    self.channels[i].last_key = 0;
+                    self.channels[i].set_effect(effect);
+                }
+
+                if timing != (state.bpm, state.ticks_per_row) {
+                    if self.timing_before_effects.is_none() {
+                        self.timing_before_effects = Some((state.bpm, state.ticks_per_row));
+                    }
+                    state.set_timing(timing.0, timing.1);
                 }
             }
         }
 
+        self.row_tick += 1;
+        if self.row_tick < state.ticks_per_row {
+            return;
+        }
+        self.row_tick = 0;
+
+        // Pattern break / position jump take effect once the row has finished
+        let jump = self.pending_jump.take();
+        let pattern_break = self.pending_break.take();
+
+        let finished_row = state.current_row;
+        if state.stop_after_row == Some(finished_row) {
+            state.stop_after_row = None;
+            self.stop_pending = true;
+            return;
+        }
+
+        // A queued pattern takes over at its launch point, before loops, jumps and breaks
+        let pattern_len = state.patterns[state.current_pattern].len();
+        if let Some(pattern) = state.queued_pattern {
+            let at_launch = match state.pattern_launch {
+                PatternLaunch::Rows(rows) => (finished_row + 1).is_multiple_of(rows),
+                _ => false,
+            };
+            if at_launch || finished_row + 1 >= pattern_len {
+                state.queued_pattern = None;
+                state.current_pattern = pattern.min(state.patterns.len() - 1);
+                state.current_row = 0;
+                Self::apply_groove(state, &mut self.groove_offset);
+                return;
+            }
+        }
+
+        // The loop region wins over jumps and breaks inside it
+        if let Some((first, last)) = state.loop_rows
+            && finished_row == last.min(pattern_len - 1)
+        {
+            state.current_row = first.min(finished_row);
+            Self::apply_groove(state, &mut self.groove_offset);
+            return;
+        }
+
+        if jump.is_some() || pattern_break.is_some() {
+            if state.play_mode == PlayMode::Song {
+                let next_order = jump.unwrap_or(Self::next_order(state));
+                Self::set_song_position(state, next_order);
+            }
+
+            let pattern_len = state.patterns[state.current_pattern].len();
+            state.current_row = pattern_break.filter(|&row| row < pattern_len).unwrap_or(0);
+            Self::apply_groove(state, &mut self.groove_offset);
+            return;
+        }
+
         state.current_row += 1;
-        if state.current_row >= ROWS_PER_PATTERN {
+        if state.current_row >= state.patterns[state.current_pattern].len() {
             state.current_row = 0;
 
             if state.play_mode == PlayMode::Song {
-                state.current_pattern += 1;
-                if state.current_pattern >= state.patterns.len() {
-                    state.current_pattern = 0;
-                }
+                let next_order = Self::next_order(state);
+                Self::set_song_position(state, next_order);
             }
         }
+        Self::apply_groove(state, &mut self.groove_offset);
+    }
+
+    // Moves the start of the row playback just advanced to by its groove offset. Only the
+    // last tick of the previous row stretches or shrinks, the tick accumulator carries the rest.
+    fn apply_groove(state: &mut SharedState, groove_offset: &mut f64) {
+        let row_samples = state.samples_per_tick * state.ticks_per_row as f64;
+        let row_offset = state
+            .groove(state.current_pattern)
+            .row_offset(state.current_row);
+        let offset = row_offset as f64 * row_samples;
+        // An earlier row may cut the previous tick short, but it stays at least one sample long
+        let longest = (state.samples_per_tick - 1.0).max(state.current_tick_samples);
+        let tick_samples = (state.current_tick_samples - (offset - *groove_offset)).min(longest);
+        *groove_offset += state.current_tick_samples - tick_samples;
+        state.current_tick_samples = tick_samples;
+    }
+
+    // Playback starting on a row delays it by that row's groove offset, if it is late.
+    fn start_groove(&mut self) {
+        self.groove_offset = 0.0;
+        Self::apply_groove(&mut self.state, &mut self.groove_offset);
+    }
+
+    // Renders `frames` frames of every channel and mixes them into the bus from frame `offset`.
+    fn render_channels(
+        &mut self,
+        bus: &mut [f32],
+        offset: usize,
+        frames: usize,
+        sample_index: u64,
+    ) {
+        // Muted channels keep running so they come back in the right state when unmuted
+        for (i, channel) in self.channels[..self.num_channels].iter_mut().enumerate() {
+            let channel_out = channel.process(frames, sample_index, &mut self.voice_bank);
+            self.inserts[i].process(channel_out, sample_index);
+            self.mixer
+                .add_channel(bus, offset * 2, i, channel_out, self.levels[i]);
+        }
+    }
+
+    // Song position after the playing one, going back to the start of the song loop at its end.
+    fn next_order(state: &SharedState) -> usize {
+        match state.loop_orders {
+            Some((first, last)) if state.current_order == last => first,
+            _ => state.current_order + 1,
+        }
+    }
+
+    // Moves to a song order position, wrapping to the start past the end of the order list.
+    fn set_song_position(state: &mut SharedState, order_pos: usize) {
+        state.current_order = if order_pos < state.order.len() {
+            order_pos
+        } else {
+            0
+        };
+        state.current_pattern = state.order[state.current_order].min(state.patterns.len() - 1);
     }
 }
 
-impl FrameProcessor<Mono> for TrackerEngine {
+// Renders interleaved stereo frames.
+impl FrameProcessor<Stereo> for TrackerEngine {
     fn process(&mut self, buffer: &mut [f32], sample_index: u64) {
         buffer.fill(0.0);
-        let frames = buffer.len();
+        let frames = buffer.len() / 2;
+
+        if let Some(old) = self.unreleased.take() {
+            self.release(old);
+        }
+        while self.unreleased.is_none()
+            && let Ok(command) = self.link.commands.try_recv()
+        {
+            self.handle_command(command);
+        }
 
-        let mut ticks_to_process = 0;
-        let is_playing;
+        let num_channels = self.state.num_channels;
 
         {
-            let mut state = self.state.lock().unwrap();
-
-            if let Some((ch_idx, key)) = state.preview_request.take()
-                && ch_idx < NUM_CHANNELS {
-                    if key > 0 {
-                        let freq = Self::midi_to_freq(key);
-                        let inst_idx = ch_idx % NUM_INSTRUMENTS;
-                        let instrument = Some(&state.instruments[inst_idx]);
-
-                        self.channels[ch_idx].trigger_note(freq, instrument);
-                        self.channels[ch_idx].last_key = key;
-                        self.preview_timers[ch_idx] = self.preview_duration;
-                    } else {
-                        self.channels[ch_idx].release();
-                        self.preview_timers[ch_idx] = 0;
-                    }
-                }
+            let state = &mut *self.state;
 
-            is_playing = state.is_playing;
-            if is_playing {
-                state.current_tick_samples += frames;
-                while state.current_tick_samples >= state.samples_per_tick {
-                    state.current_tick_samples -= state.samples_per_tick;
-                    ticks_to_process += 1;
+            // Channels removed from the project fall silent, ready to be added again
+            if num_channels < self.num_channels {
+                for channel in &mut self.channels[num_channels..self.num_channels] {
+                    channel.cut();
+                    channel.silence();
+                    channel.return_voices(&mut self.voice_bank);
                 }
             }
+            self.num_channels = num_channels;
+
+            for (i, channel) in self.channels[..num_channels].iter_mut().enumerate() {
+                let settings = &state.channel_settings[i];
+                channel.set_voice_limit(settings.voice_limit);
+                channel.set_voice_stealing(settings.voice_stealing);
+                channel.set_default_pan(settings.pan);
+                channel.set_bpm(state.bpm);
+                self.inserts[i].update(&settings.inserts);
+                self.levels[i] = StripLevels {
+                    volume: if state.is_channel_audible(i) {
+                        settings.volume
+                    } else {
+                        0.0
+                    },
+                    reverb_send: settings.reverb_send,
+                    delay_send: settings.delay_send,
+                };
+            }
+            self.master_volume = state.master_volume;
+            self.mixer.set_sends(&state.sends, state.bpm);
+
+            if self.was_playing
+                && !state.is_playing
+                && let Some((bpm, ticks_per_row)) = self.timing_before_effects.take()
+            {
+                state.set_timing(bpm, ticks_per_row);
+            }
         }
+        let is_playing = self.state.is_playing;
 
         if self.was_playing && !is_playing {
             for channel in &mut self.channels {
                 channel.silence();
             }
         }
+        if !self.was_playing && is_playing {
+            self.row_tick = 0;
+            self.delayed_notes.fill(None);
+            self.pending_jump = None;
+            self.pending_break = None;
+            self.stop_pending = false;
+            self.start_groove();
+        }
         self.was_playing = is_playing;
 
         if !is_playing {
-            for i in 0..NUM_CHANNELS {
+            for i in 0..num_channels {
                 if self.preview_timers[i] > 0 {
                     if self.preview_timers[i] > frames {
                         self.preview_timers[i] -= frames;
@@ -160,29 +567,53 @@ impl FrameProcessor<Mono> for TrackerEngine {
                     }
                 }
             }
-        } else {
-            for _ in 0..ticks_to_process {
-                self.tick();
-            }
         }
 
-        for channel in &mut self.channels {
-            let channel_out = channel.process(frames, sample_index);
-            for (i, sample) in buffer.iter_mut().enumerate() {
-                *sample += channel_out[i];
+        // The block is split where ticks fall, so notes start on their exact sample whatever
+        // the buffer size, and live playback times rows exactly like an export
+        self.mixer.begin_block(buffer.len());
+        let mut offset = 0;
+        while offset < frames {
+            let mut segment = frames - offset;
+            while self.state.is_playing
+                && self.state.current_tick_samples >= self.state.samples_per_tick
+            {
+                self.state.current_tick_samples -= self.state.samples_per_tick;
+                self.tick();
+            }
+            if self.state.is_playing {
+                // The next tick starts on the first sample at or after its exact time
+                let state = &mut *self.state;
+                let until_tick = (state.samples_per_tick - state.current_tick_samples).ceil();
+                segment = segment.min(until_tick as usize);
+                state.current_tick_samples += segment as f64;
             }
-        }
 
-        for sample in buffer.iter_mut() {
-            *sample = sample.clamp(-1.0, 1.0);
+            self.render_channels(buffer, offset, segment, sample_index + offset as u64);
+            offset += segment;
         }
+
+        // The send effects and limiter run on the whole block
+        self.mixer
+            .process_master(buffer, self.master_volume, sample_index);
+
+        self.link.status.publish(
+            &self.state,
+            &self.mixer.channel_peaks()[..num_channels],
+            self.mixer.master_peak(),
+        );
     }
 
     fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        self.mixer.set_sample_rate(sample_rate);
         for channel in &mut self.channels {
             channel.set_sample_rate(sample_rate);
         }
+        self.voice_bank.set_sample_rate(sample_rate);
+        for inserts in &mut self.inserts {
+            inserts.set_sample_rate(sample_rate);
+        }
     }
 
     fn latency_samples(&self) -> u32 {
@@ -197,3 +628,341 @@ impl FrameProcessor<Mono> for TrackerEngine {
         "TrackerEngine".to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::groove::Groove;
+    use crate::core::pattern::Pattern;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    // Frames per `process` call; the master reverb needs a multiple of 4
+    const BLOCK_FRAMES: usize = 512;
+
+    fn note(key: u8, instrument: u8) -> Note {
+        Note {
+            key,
+            instrument: Some(instrument),
+            ..Default::default()
+        }
+    }
+
+    fn start(state: &mut SharedState) -> TrackerEngine {
+        state.play_at(0, state.order[0], 0);
+        TrackerEngine::new(state.sample_rate, state.clone()).0
+    }
+
+    // Frames at which the first `rows` rows start, rendering one frame at a time.
+    fn row_starts(engine: &mut TrackerEngine, rows: usize) -> Vec<usize> {
+        let mut buffer = [0.0; 2];
+        let mut starts = Vec::with_capacity(rows);
+        let mut frame = 0;
+        while starts.len() < rows {
+            let before = (engine.row_tick, engine.state.current_row);
+            engine.process(&mut buffer, 0);
+            // The first tick of a row moves on to the second one, or to the next row
+            if before.0 == 0 && (engine.row_tick, engine.state.current_row) != before {
+                starts.push(frame);
+            }
+            frame += 1;
+        }
+        starts
+    }
+
+    // Renders `frames` frames and returns the loudest sample.
+    fn render_peak(engine: &mut TrackerEngine, frames: usize) -> f32 {
+        let mut buffer = vec![0.0; BLOCK_FRAMES * 2];
+        let mut peak = 0.0f32;
+        for _ in 0..frames / BLOCK_FRAMES {
+            engine.process(&mut buffer, 0);
+            peak = buffer.iter().fold(peak, |peak, s| peak.max(s.abs()));
+        }
+        peak
+    }
+
+    // Renders `frames` frames, cycling through the given `process` call sizes.
+    fn render(state: &SharedState, block_sizes: &[usize], frames: usize) -> Vec<f32> {
+        let mut engine = start(&mut state.clone());
+        let mut output = Vec::with_capacity(frames * 2);
+        for &size in block_sizes.iter().cycle() {
+            let size = size.min(frames - output.len() / 2);
+            if size == 0 {
+                break;
+            }
+            let mut buffer = vec![0.0; size * 2];
+            engine.process(&mut buffer, 0);
+            output.extend_from_slice(&buffer);
+        }
+        output
+    }
+
+    // Frames where sound starts after silence.
+    fn onsets(output: &[f32]) -> Vec<usize> {
+        let mut onsets = Vec::new();
+        let mut silent = true;
+        for (frame, samples) in output.chunks(2).enumerate() {
+            let sounding = samples.iter().any(|&s| s != 0.0);
+            if sounding && silent {
+                onsets.push(frame);
+            }
+            silent = !sounding;
+        }
+        onsets
+    }
+
+    fn first_difference(a: &[f32], b: &[f32]) -> Option<usize> {
+        a.iter().zip(b).position(|(a, b)| a != b).map(|i| i / 2)
+    }
+
+    // A bass note on every fourth row, cut two rows later.
+    fn staccato_song() -> SharedState {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 4);
+        let rows = &mut state.patterns[0].rows;
+        for row in (0..16).step_by(4) {
+            rows[row][0] = note(48 + row as u8, 3);
+            rows[row + 2][0] = Note {
+                key: NOTE_CUT,
+                ..Default::default()
+            };
+        }
+        state
+    }
+
+    #[test]
+    fn output_does_not_depend_on_the_block_size() {
+        let mut state = staccato_song();
+        let row_frames = 6000;
+        let frames = row_frames * 16;
+
+        let whole = render(&state, &[frames], frames);
+        let chunked = render(&state, &[1, 7, 512], frames);
+        let starts = onsets(&whole);
+        assert_eq!(starts.len(), 4);
+        for (&start, row) in starts.iter().zip((0..).step_by(4)) {
+            // The envelope may take a few frames to leave zero
+            assert!((row * row_frames..row * row_frames + 8).contains(&start));
+        }
+        assert_eq!(onsets(&chunked), starts);
+        assert_eq!(first_difference(&whole, &chunked), None);
+
+        // The sends too
+        state.channel_settings[0].reverb_send = 0.5;
+        state.channel_settings[0].delay_send = 0.5;
+        let whole = render(&state, &[frames], frames);
+        let chunked = render(&state, &[1, 7, 512], frames);
+        assert_eq!(first_difference(&whole, &chunked), None);
+    }
+
+    #[test]
+    fn fractional_tempo_does_not_drift() {
+        // A low rate keeps rendering frame by frame quick
+        let (sample_rate, bpm) = (2000.0, 137.3);
+        let mut state = SharedState::new(bpm, sample_rate);
+        state.set_timing(bpm, 3);
+        let mut engine = start(&mut state);
+
+        let starts = row_starts(&mut engine, 300);
+        for (row, &start) in starts.iter().enumerate() {
+            let exact = row as f64 * sample_rate as f64 * 60.0 / (bpm as f64 * 4.0);
+            assert!(
+                (start as f64 - exact).abs() < 1.0,
+                "row {row} at {start}, not {exact}"
+            );
+        }
+    }
+
+    // 60-frame ticks, 4 to a row, at a sample rate low enough to render frame by frame.
+    fn groove_song(groove: Groove) -> SharedState {
+        let mut state = SharedState::new(125.0, 2000.0);
+        state.set_timing(125.0, 4);
+        state.groove = groove;
+        state
+    }
+
+    #[test]
+    fn swing_delays_every_second_row() {
+        let mut state = groove_song(Groove {
+            swing: 0.125,
+            ..Default::default()
+        });
+        let mut engine = start(&mut state);
+
+        let starts = row_starts(&mut engine, 16);
+        let expected: Vec<usize> = (0..16).map(|row| row * 240 + row % 2 * 30).collect();
+        assert_eq!(starts, expected);
+    }
+
+    #[test]
+    fn early_rows_leave_the_previous_tick_one_frame() {
+        // Every second row wants to start together with the one before it
+        let mut state = groove_song(Groove {
+            swing: 0.0,
+            offsets: vec![0.5, -0.5],
+        });
+        let mut engine = start(&mut state);
+
+        let starts = row_starts(&mut engine, 16);
+        let expected: Vec<usize> = (0..16)
+            .map(|row| match row % 2 {
+                0 => row * 240 + 120,
+                // Three whole ticks and one frame after the row before
+                _ => (row - 1) * 240 + 120 + 3 * 60 + 1,
+            })
+            .collect();
+        assert_eq!(starts, expected);
+    }
+
+    // Runs `ticks` ticks and returns the pattern and row of every row played.
+    fn played_rows(engine: &mut TrackerEngine, ticks: usize) -> Vec<(usize, usize)> {
+        let mut played = Vec::new();
+        for _ in 0..ticks {
+            let first_tick = engine.row_tick == 0;
+            let position = (engine.state.current_pattern, engine.state.current_row);
+            engine.tick();
+            if first_tick && engine.state.is_playing {
+                played.push(position);
+            }
+        }
+        played
+    }
+
+    fn rows(pattern: usize, rows: &[usize]) -> Vec<(usize, usize)> {
+        rows.iter().map(|&row| (pattern, row)).collect()
+    }
+
+    #[test]
+    fn playback_stops_after_the_last_selected_row() {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 2);
+        let mut engine = start(&mut state);
+        engine.state.current_row = 2;
+        engine.state.stop_after_row = Some(5);
+
+        assert_eq!(played_rows(&mut engine, 20), rows(0, &[2, 3, 4, 5]));
+        assert!(!engine.state.is_playing);
+        assert_eq!(engine.state.stop_after_row, None);
+    }
+
+    #[test]
+    fn row_loop_wraps_at_its_last_row() {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 2);
+        state.loop_rows = Some((4, 7));
+        let mut engine = start(&mut state);
+        engine.state.current_row = 4;
+
+        let expected = rows(0, &[4, 5, 6, 7, 4, 5, 6, 7, 4, 5]);
+        assert_eq!(played_rows(&mut engine, 20), expected);
+    }
+
+    #[test]
+    fn order_loop_wraps_at_its_last_position() {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 2);
+        state.patterns = (0..3)
+            .map(|_| Pattern::new(2, state.num_channels))
+            .collect();
+        // Each order position plays the pattern of the same number
+        state.order = vec![0, 1, 2];
+        state.play_mode = PlayMode::Song;
+        state.loop_orders = Some((1, 2));
+        let mut engine = start(&mut state);
+
+        let expected = [
+            (0, 0),
+            (0, 1),
+            (1, 0),
+            (1, 1),
+            (2, 0),
+            (2, 1),
+            (1, 0),
+            (1, 1),
+        ];
+        assert_eq!(played_rows(&mut engine, 16), expected);
+    }
+
+    #[test]
+    fn stop_wins_over_a_row_loop() {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 2);
+        state.loop_rows = Some((0, 3));
+        let mut engine = start(&mut state);
+
+        // Stopping inside the loop, and on its last row
+        engine.state.stop_after_row = Some(2);
+        assert_eq!(played_rows(&mut engine, 20), rows(0, &[0, 1, 2]));
+
+        engine.state.play_at(0, 0, 0);
+        engine.state.stop_after_row = Some(3);
+        assert_eq!(played_rows(&mut engine, 20), rows(0, &[0, 1, 2, 3]));
+        assert!(!engine.state.is_playing);
+    }
+
+    // Plays rows 0 to 2 of the first of two 8-row patterns, then picks the second one.
+    fn switch_pattern(launch: PatternLaunch) -> TrackerEngine {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 2);
+        state.patterns = (0..2)
+            .map(|_| Pattern::new(8, state.num_channels))
+            .collect();
+        state.pattern_launch = launch;
+        let mut engine = start(&mut state);
+
+        assert_eq!(played_rows(&mut engine, 6), rows(0, &[0, 1, 2]));
+        engine.handle_command(EngineCommand::SwitchPattern(1));
+        engine
+    }
+
+    #[test]
+    fn immediate_launch_switches_at_the_playing_row() {
+        let mut engine = switch_pattern(PatternLaunch::Immediate);
+        assert_eq!(engine.state.queued_pattern, None);
+        assert_eq!(played_rows(&mut engine, 4), rows(1, &[3, 4]));
+    }
+
+    #[test]
+    fn pattern_end_launch_waits_for_the_last_row() {
+        let mut engine = switch_pattern(PatternLaunch::PatternEnd);
+        assert_eq!(engine.state.queued_pattern, Some(1));
+
+        let mut expected = rows(0, &[3, 4, 5, 6, 7]);
+        expected.extend(rows(1, &[0, 1]));
+        assert_eq!(played_rows(&mut engine, 14), expected);
+        assert_eq!(engine.state.queued_pattern, None);
+    }
+
+    #[test]
+    fn rows_launch_waits_for_the_next_multiple() {
+        let mut engine = switch_pattern(PatternLaunch::Rows(4));
+        assert_eq!(engine.state.queued_pattern, Some(1));
+
+        let mut expected = rows(0, &[3]);
+        expected.extend(rows(1, &[0, 1, 2, 3, 4]));
+        assert_eq!(played_rows(&mut engine, 12), expected);
+        assert_eq!(engine.state.queued_pattern, None);
+    }
+
+    #[test]
+    fn note_cut_effect_lets_the_same_key_play_again() {
+        let mut state = SharedState::new(120.0, SAMPLE_RATE);
+        state.set_timing(120.0, 4);
+        state.channel_settings[0].voice_mode = VoiceMode::Legato;
+        let bass = 3;
+        let rows = &mut state.patterns[0].rows;
+        // C-4 cut on tick 1, then C-4 again on the next row
+        rows[0][0] = Note {
+            effect: 0xE,
+            effect_param: 0xC1,
+            ..note(60, bass)
+        };
+        rows[1][0] = note(60, bass);
+        // Rows are 6000 frames long, the cut comes at frame 1500
+        let mut engine = start(&mut state);
+
+        assert!(render_peak(&mut engine, BLOCK_FRAMES) > 0.01);
+        render_peak(&mut engine, BLOCK_FRAMES * 4);
+        assert_eq!(render_peak(&mut engine, BLOCK_FRAMES * 6), 0.0);
+        assert!(render_peak(&mut engine, BLOCK_FRAMES * 2) > 0.01);
+    }
+}
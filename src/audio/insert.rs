@@ -0,0 +1,227 @@
+use infinitedsp_core::core::audio_param::AudioParam;
+use infinitedsp_core::core::channels::{DualMono, Stereo};
+use infinitedsp_core::core::frame_processor::FrameProcessor;
+use infinitedsp_core::core::parameter::Parameter;
+use infinitedsp_core::effects::dynamics::compressor::Compressor;
+use infinitedsp_core::effects::dynamics::distortion::{Distortion, DistortionType};
+use infinitedsp_core::effects::filter::state_variable::{StateVariableFilter, SvfType};
+use infinitedsp_core::effects::modulation::modulated_delay::ModulatedDelay;
+
+use crate::core::InsertConfig;
+use crate::core::insert::{DistortionMode, FilterMode};
+
+struct RuntimeInsert {
+    processor: Box<dyn FrameProcessor<Stereo> + Send>,
+    params: Vec<Parameter>,
+    config_type: InsertConfig,
+}
+
+/// A channel's insert effects; each effect runs as a pair of mono processors sharing parameters.
+pub struct InsertChain {
+    inserts: Vec<RuntimeInsert>,
+    sample_rate: f32,
+}
+
+// Whether two effects run on the same processors, only differing in parameters.
+fn same_kind(a: &InsertConfig, b: &InsertConfig) -> bool {
+    match (a, b) {
+        (InsertConfig::Filter { mode: m1, .. }, InsertConfig::Filter { mode: m2, .. }) => m1 == m2,
+        (InsertConfig::Distortion { mode: m1, .. }, InsertConfig::Distortion { mode: m2, .. }) => {
+            m1 == m2
+        }
+        (InsertConfig::Chorus { .. }, InsertConfig::Chorus { .. }) => true,
+        (InsertConfig::Compressor { .. }, InsertConfig::Compressor { .. }) => true,
+        _ => false,
+    }
+}
+
+// Whether a chain built for `built` can follow `configs` by only setting parameters.
+pub fn same_layout(built: &[InsertConfig], configs: &[InsertConfig]) -> bool {
+    built.len() == configs.len() && built.iter().zip(configs).all(|(a, b)| same_kind(a, b))
+}
+
+impl InsertChain {
+    // Built on the UI thread; the engine only swaps chains in.
+    pub fn new(sample_rate: f32, configs: &[InsertConfig]) -> Self {
+        let mut chain = Self {
+            inserts: Vec::with_capacity(configs.len()),
+            sample_rate,
+        };
+        chain.build(configs);
+        chain
+    }
+
+    // Chorus depth is set in milliseconds but the delay line modulates in samples.
+    fn depth_samples(depth_ms: f32, sample_rate: f32) -> f32 {
+        depth_ms * 0.001 * sample_rate
+    }
+
+    fn build(&mut self, configs: &[InsertConfig]) {
+        for config in configs {
+            let (mut processor, params): (Box<dyn FrameProcessor<Stereo> + Send>, _) = match config
+            {
+                InsertConfig::Filter { mode, cutoff, q } => {
+                    let svf_type = match mode {
+                        FilterMode::LowPass => SvfType::LowPass,
+                        FilterMode::HighPass => SvfType::HighPass,
+                        FilterMode::BandPass => SvfType::BandPass,
+                        FilterMode::Notch => SvfType::Notch,
+                    };
+                    let p_cutoff = Parameter::new(*cutoff);
+                    let p_q = Parameter::new(*q);
+                    let new_filter = || {
+                        StateVariableFilter::new(
+                            svf_type,
+                            AudioParam::Linked(p_cutoff.clone()),
+                            AudioParam::Linked(p_q.clone()),
+                        )
+                    };
+                    let filter = DualMono::new(new_filter(), new_filter());
+                    (Box::new(filter), vec![p_cutoff, p_q])
+                }
+                InsertConfig::Distortion { mode, drive, mix } => {
+                    let p_drive = Parameter::new(*drive);
+                    let p_mix = Parameter::new(*mix);
+                    let new_distortion = || {
+                        let dist_type = match mode {
+                            DistortionMode::SoftClip => DistortionType::SoftClip,
+                            DistortionMode::HardClip => DistortionType::HardClip,
+                            DistortionMode::Foldback => DistortionType::Foldback,
+                        };
+                        Distortion::new(
+                            AudioParam::Linked(p_drive.clone()),
+                            AudioParam::Linked(p_mix.clone()),
+                            dist_type,
+                        )
+                    };
+                    let distortion = DualMono::new(new_distortion(), new_distortion());
+                    (Box::new(distortion), vec![p_drive, p_mix])
+                }
+                InsertConfig::Chorus {
+                    depth,
+                    feedback,
+                    mix,
+                } => {
+                    let p_depth = Parameter::new(Self::depth_samples(*depth, self.sample_rate));
+                    let p_feedback = Parameter::new(*feedback);
+                    let p_mix = Parameter::new(*mix);
+                    let new_chorus = || {
+                        let mut chorus = ModulatedDelay::new_chorus();
+                        chorus.set_depth(AudioParam::Linked(p_depth.clone()));
+                        chorus.set_feedback(AudioParam::Linked(p_feedback.clone()));
+                        chorus.set_mix(AudioParam::Linked(p_mix.clone()));
+                        chorus
+                    };
+                    let chorus = DualMono::new(new_chorus(), new_chorus());
+                    (Box::new(chorus), vec![p_depth, p_feedback, p_mix])
+                }
+                InsertConfig::Compressor {
+                    threshold,
+                    ratio,
+                    attack,
+                    release,
+                    makeup,
+                } => {
+                    let p_threshold = Parameter::new(*threshold);
+                    let p_ratio = Parameter::new(*ratio);
+                    let p_attack = Parameter::new(*attack);
+                    let p_release = Parameter::new(*release);
+                    let p_makeup = Parameter::new(*makeup);
+                    let new_compressor = || {
+                        let mut compressor = Compressor::new(
+                            AudioParam::Linked(p_threshold.clone()),
+                            AudioParam::Linked(p_ratio.clone()),
+                        );
+                        compressor.set_attack(AudioParam::Linked(p_attack.clone()));
+                        compressor.set_release(AudioParam::Linked(p_release.clone()));
+                        compressor.set_makeup(AudioParam::Linked(p_makeup.clone()));
+                        compressor
+                    };
+                    let compressor = DualMono::new(new_compressor(), new_compressor());
+                    (
+                        Box::new(compressor),
+                        vec![p_threshold, p_ratio, p_attack, p_release, p_makeup],
+                    )
+                }
+            };
+            processor.set_sample_rate(self.sample_rate);
+
+            self.inserts.push(RuntimeInsert {
+                processor,
+                params,
+                config_type: config.clone(),
+            });
+        }
+    }
+
+    // Follows the channel's parameter changes. A chain whose effects or modes no longer match
+    // is left alone until its replacement arrives with the song.
+    pub fn update(&mut self, configs: &[InsertConfig]) {
+        let matches = self.inserts.len() == configs.len()
+            && self
+                .inserts
+                .iter()
+                .zip(configs)
+                .all(|(rt, cfg)| same_kind(&rt.config_type, cfg));
+        if !matches {
+            return;
+        }
+
+        for (rt, cfg) in self.inserts.iter_mut().zip(configs) {
+            if rt.config_type == *cfg {
+                continue;
+            }
+            let params = &rt.params;
+            match cfg {
+                InsertConfig::Filter { cutoff, q, .. } => {
+                    params[0].set(*cutoff);
+                    params[1].set(*q);
+                }
+                InsertConfig::Distortion { drive, mix, .. } => {
+                    params[0].set(*drive);
+                    params[1].set(*mix);
+                }
+                InsertConfig::Chorus {
+                    depth,
+                    feedback,
+                    mix,
+                } => {
+                    params[0].set(Self::depth_samples(*depth, self.sample_rate));
+                    params[1].set(*feedback);
+                    params[2].set(*mix);
+                }
+                InsertConfig::Compressor {
+                    threshold,
+                    ratio,
+                    attack,
+                    release,
+                    makeup,
+                } => {
+                    params[0].set(*threshold);
+                    params[1].set(*ratio);
+                    params[2].set(*attack);
+                    params[3].set(*release);
+                    params[4].set(*makeup);
+                }
+            }
+            rt.config_type = cfg.clone();
+        }
+    }
+
+    // Processes interleaved stereo frames in place.
+    pub fn process(&mut self, buffer: &mut [f32], sample_index: u64) {
+        for insert in &mut self.inserts {
+            insert.processor.process(buffer, sample_index);
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        for insert in &mut self.inserts {
+            insert.processor.set_sample_rate(sample_rate);
+            if let InsertConfig::Chorus { depth, .. } = insert.config_type {
+                insert.params[0].set(Self::depth_samples(depth, sample_rate));
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use super::engine::MAX_BLOCK_FRAMES;
+use crate::core::sends::{MAX_DELAY_SECONDS, SendSettings};
+use infinitedsp_core::core::audio_param::AudioParam;
+use infinitedsp_core::core::channels::{DualMono, Stereo};
+use infinitedsp_core::core::frame_processor::FrameProcessor;
+use infinitedsp_core::core::parameter::Parameter;
+use infinitedsp_core::effects::time::delay::Delay;
+use infinitedsp_core::effects::time::reverb::Reverb;
+
+// The limiter keeps the master bus below the threshold, easing in over the knee.
+const LIMITER_THRESHOLD_DB: f32 = -1.0;
+const LIMITER_KNEE_DB: f32 = 6.0;
+const LIMITER_RELEASE_MS: f32 = 100.0;
+// Time for a level meter to fall by a factor of e.
+const METER_FALL_SECONDS: f32 = 0.3;
+// The reverb only takes whole groups of this many frames, so its return lags the bus by one
+// group less, whatever the block size.
+const REVERB_GROUP_FRAMES: usize = 4;
+const REVERB_LAG_SAMPLES: usize = (REVERB_GROUP_FRAMES - 1) * 2;
+
+fn to_db(level: f32) -> f32 {
+    20.0 * level.max(1.0e-9).log10()
+}
+
+fn from_db(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Stereo-linked peak limiter with a soft knee and instant attack.
+pub struct Limiter {
+    envelope: f32,
+    release_coeff: f32,
+    knee_start: f32,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut limiter = Self {
+            envelope: 0.0,
+            release_coeff: 0.0,
+            knee_start: from_db(LIMITER_THRESHOLD_DB - LIMITER_KNEE_DB / 2.0),
+        };
+        FrameProcessor::<Stereo>::set_sample_rate(&mut limiter, sample_rate);
+        limiter
+    }
+
+    // Gain change in dB for an input level in dB (infinite ratio above the knee).
+    fn gain_db(level_db: f32) -> f32 {
+        let over = level_db - LIMITER_THRESHOLD_DB;
+        if 2.0 * over < -LIMITER_KNEE_DB {
+            0.0
+        } else if 2.0 * over <= LIMITER_KNEE_DB {
+            let x = over + LIMITER_KNEE_DB / 2.0;
+            -(x * x) / (2.0 * LIMITER_KNEE_DB)
+        } else {
+            -over
+        }
+    }
+}
+
+impl FrameProcessor<Stereo> for Limiter {
+    fn process(&mut self, buffer: &mut [f32], _sample_index: u64) {
+        for frame in buffer.chunks_mut(2) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            self.envelope = peak.max(self.envelope * self.release_coeff);
+
+            if self.envelope > self.knee_start {
+                let gain = from_db(Self::gain_db(to_db(self.envelope)));
+                for sample in frame.iter_mut() {
+                    *sample *= gain;
+                }
+            }
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.release_coeff = (-1.0 / (LIMITER_RELEASE_MS * 0.001 * sample_rate)).exp();
+    }
+
+    fn name(&self) -> &str {
+        "Limiter"
+    }
+}
+
+/// Per-block gains of one channel strip.
+#[derive(Clone, Copy)]
+pub struct StripLevels {
+    pub volume: f32,
+    pub reverb_send: f32,
+    pub delay_send: f32,
+}
+
+impl Default for StripLevels {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            reverb_send: 0.0,
+            delay_send: 0.0,
+        }
+    }
+}
+
+/// Reverb and tempo-synced stereo delay, fed by the channel sends and returned to the master bus.
+struct SendEffects {
+    reverb: Reverb,
+    room_size: Parameter,
+    damping: Parameter,
+    delay: DualMono<Delay, Delay>,
+    delay_left: Parameter,
+    delay_right: Parameter,
+    feedback: Parameter,
+    reverb_bus: Vec<f32>,
+    // Reverb input short of a whole group, and reverb output the bus has not reached yet
+    reverb_pending: Vec<f32>,
+    reverb_ready: Vec<f32>,
+    delay_bus: Vec<f32>,
+    reverb_return: f32,
+    delay_return: f32,
+}
+
+impl SendEffects {
+    fn new(sample_rate: f32) -> Self {
+        let room_size = Parameter::new(0.0);
+        let damping = Parameter::new(0.0);
+        let delay_left = Parameter::new(0.0);
+        let delay_right = Parameter::new(0.0);
+        let feedback = Parameter::new(0.0);
+
+        // Both are fully wet, the dry signal stays on the master bus
+        let reverb = Reverb::new_with_params(
+            AudioParam::Linked(room_size.clone()),
+            AudioParam::Linked(damping.clone()),
+            0,
+        );
+        let new_delay = |time: &Parameter| {
+            Delay::new(
+                MAX_DELAY_SECONDS,
+                AudioParam::Linked(time.clone()),
+                AudioParam::Linked(feedback.clone()),
+                AudioParam::Static(1.0),
+            )
+        };
+        let delay = DualMono::new(new_delay(&delay_left), new_delay(&delay_right));
+
+        let mut sends = Self {
+            reverb,
+            room_size,
+            damping,
+            delay,
+            delay_left,
+            delay_right,
+            feedback,
+            reverb_bus: Vec::with_capacity(MAX_BLOCK_FRAMES * 2),
+            reverb_pending: Vec::with_capacity(MAX_BLOCK_FRAMES * 2 + REVERB_LAG_SAMPLES),
+            reverb_ready: Vec::with_capacity(MAX_BLOCK_FRAMES * 2 + REVERB_LAG_SAMPLES),
+            delay_bus: Vec::with_capacity(MAX_BLOCK_FRAMES * 2),
+            reverb_return: 0.0,
+            delay_return: 0.0,
+        };
+        sends.reverb_ready.resize(REVERB_LAG_SAMPLES, 0.0);
+        sends.set_sample_rate(sample_rate);
+        sends
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        FrameProcessor::<Stereo>::set_sample_rate(&mut self.reverb, sample_rate);
+        FrameProcessor::<Stereo>::set_sample_rate(&mut self.delay, sample_rate);
+    }
+
+    fn update(&mut self, settings: &SendSettings, bpm: f32) {
+        self.room_size.set(settings.reverb_room_size);
+        self.damping.set(settings.reverb_damping);
+        self.delay_left
+            .set(SendSettings::delay_seconds(settings.delay_rows_left, bpm));
+        self.delay_right
+            .set(SendSettings::delay_seconds(settings.delay_rows_right, bpm));
+        self.feedback.set(settings.delay_feedback);
+        self.reverb_return = settings.reverb_return;
+        self.delay_return = settings.delay_return;
+    }
+
+    fn begin_block(&mut self, len: usize) {
+        self.reverb_bus.clear();
+        self.reverb_bus.resize(len, 0.0);
+        self.delay_bus.clear();
+        self.delay_bus.resize(len, 0.0);
+    }
+
+    // Runs the reverb on the whole groups received so far and replaces the reverb bus with the
+    // output that is due.
+    fn process_reverb(&mut self, sample_index: u64) {
+        self.reverb_pending.extend_from_slice(&self.reverb_bus);
+        let group = REVERB_GROUP_FRAMES * 2;
+        let whole = self.reverb_pending.len() / group * group;
+        if whole > 0 {
+            let input = &mut self.reverb_pending[..whole];
+            FrameProcessor::<Stereo>::process(&mut self.reverb, input, sample_index);
+            self.reverb_ready.extend_from_slice(input);
+            self.reverb_pending.drain(..whole);
+        }
+
+        let due = self.reverb_bus.len();
+        self.reverb_bus.copy_from_slice(&self.reverb_ready[..due]);
+        self.reverb_ready.drain(..due);
+    }
+
+    // Adds the wet signals to `bus`.
+    fn process(&mut self, bus: &mut [f32], sample_index: u64) {
+        self.process_reverb(sample_index);
+        FrameProcessor::<Stereo>::process(&mut self.delay, &mut self.delay_bus, sample_index);
+
+        for ((out, reverb), delay) in bus.iter_mut().zip(&self.reverb_bus).zip(&self.delay_bus) {
+            *out += reverb * self.reverb_return + delay * self.delay_return;
+        }
+    }
+}
+
+/// Sums channels through their faders into the master bus and keeps level meters.
+pub struct Mixer {
+    sends: SendEffects,
+    limiter: Limiter,
+    channel_peaks: Vec<f32>,
+    master_peak: f32,
+    sample_rate: f32,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: f32, num_channels: usize) -> Self {
+        Self {
+            sends: SendEffects::new(sample_rate),
+            limiter: Limiter::new(sample_rate),
+            channel_peaks: vec![0.0; num_channels],
+            master_peak: 0.0,
+            sample_rate,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.sends.set_sample_rate(sample_rate);
+        FrameProcessor::<Stereo>::set_sample_rate(&mut self.limiter, sample_rate);
+    }
+
+    pub fn set_sends(&mut self, settings: &SendSettings, bpm: f32) {
+        self.sends.update(settings, bpm);
+    }
+
+    /// Prepares the send buses for a block of `bus_len` interleaved samples.
+    pub fn begin_block(&mut self, bus_len: usize) {
+        self.sends.begin_block(bus_len);
+    }
+
+    fn meter_decay(&self, frames: usize) -> f32 {
+        (-(frames as f32) / (METER_FALL_SECONDS * self.sample_rate)).exp()
+    }
+
+    fn block_peak(buffer: &[f32]) -> f32 {
+        buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    /// Adds a channel's stereo output to the bus and the sends, starting `offset` samples into
+    /// the block; a volume of 0 only lets its meter fall.
+    pub fn add_channel(
+        &mut self,
+        bus: &mut [f32],
+        offset: usize,
+        channel: usize,
+        input: &[f32],
+        levels: StripLevels,
+    ) {
+        let decay = self.meter_decay(input.len() / 2);
+        let mut peak = 0.0;
+
+        if levels.volume > 0.0 {
+            let reverb_gain = levels.volume * levels.reverb_send;
+            let delay_gain = levels.volume * levels.delay_send;
+            let end = (offset + input.len()).min(bus.len());
+            for (i, &sample) in (offset..end).zip(input) {
+                bus[i] += sample * levels.volume;
+                self.sends.reverb_bus[i] += sample * reverb_gain;
+                self.sends.delay_bus[i] += sample * delay_gain;
+            }
+            peak = Self::block_peak(input) * levels.volume;
+        }
+
+        if let Some(meter) = self.channel_peaks.get_mut(channel) {
+            *meter = peak.max(*meter * decay);
+        }
+    }
+
+    /// Adds the send returns, then applies master volume and the limiter to the summed bus.
+    pub fn process_master(&mut self, bus: &mut [f32], master_volume: f32, sample_index: u64) {
+        self.sends.process(bus, sample_index);
+
+        for sample in bus.iter_mut() {
+            *sample *= master_volume;
+        }
+        FrameProcessor::<Stereo>::process(&mut self.limiter, bus, sample_index);
+
+        let decay = self.meter_decay(bus.len() / 2);
+        self.master_peak = Self::block_peak(bus).max(self.master_peak * decay);
+    }
+
+    pub fn channel_peaks(&self) -> &[f32] {
+        &self.channel_peaks
+    }
+
+    pub fn master_peak(&self) -> f32 {
+        self.master_peak
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gain_db_leaves_levels_below_the_knee_alone() {
+        let knee_start = LIMITER_THRESHOLD_DB - LIMITER_KNEE_DB / 2.0;
+        assert_eq!(Limiter::gain_db(-40.0), 0.0);
+        assert_eq!(Limiter::gain_db(knee_start - 0.01), 0.0);
+        assert!(Limiter::gain_db(knee_start).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn gain_db_holds_levels_above_the_knee_at_the_threshold() {
+        let knee_end = LIMITER_THRESHOLD_DB + LIMITER_KNEE_DB / 2.0;
+        for level_db in [knee_end + 0.01, 6.0, 20.0] {
+            assert!((level_db + Limiter::gain_db(level_db) - LIMITER_THRESHOLD_DB).abs() < 1.0e-4);
+        }
+        // The knee joins both sides without a jump
+        assert!((Limiter::gain_db(knee_end) - (LIMITER_THRESHOLD_DB - knee_end)).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn gain_db_output_level_never_falls_as_the_input_rises() {
+        let mut last_out = f32::MIN;
+        for step in 0..400 {
+            let level_db = -20.0 + step as f32 * 0.1;
+            let out = level_db + Limiter::gain_db(level_db);
+            assert!(out >= last_out - 1.0e-5, "{} dB", level_db);
+            last_out = out;
+        }
+    }
+
+    #[test]
+    fn limiter_keeps_peaks_below_the_threshold() {
+        let mut limiter = Limiter::new(44100.0);
+        let mut buffer: Vec<f32> = (0..1024).map(|i| 4.0 * (i as f32 * 0.05).sin()).collect();
+        FrameProcessor::<Stereo>::process(&mut limiter, &mut buffer, 0);
+        let peak = buffer.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak <= from_db(LIMITER_THRESHOLD_DB) + 1.0e-4);
+    }
+}
@@ -1,7 +1,11 @@
 pub mod channel;
+pub mod control;
 pub mod engine;
+pub mod insert;
+pub mod mixer;
 pub mod render;
 pub mod voice;
 
-pub use engine::TrackerEngine;
+pub use control::{EngineCommand, EngineHandle};
+pub use engine::{MAX_BLOCK_FRAMES, TrackerEngine};
 pub use render::render_to_wav;
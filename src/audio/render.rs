@@ -1,34 +1,50 @@
 use crate::audio::TrackerEngine;
-use crate::core::{ROWS_PER_PATTERN, SharedState};
+use crate::core::SharedState;
+use crate::core::state::{PatternLaunch, PlayMode};
 use hound;
-use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::core::channels::Stereo;
 use infinitedsp_core::core::frame_processor::FrameProcessor;
-use std::sync::{Arc, Mutex};
 
-pub fn render_to_wav(path: &str, state: &SharedState) -> Result<(), Box<dyn std::error::Error>> {
+// Longest reverb / delay tail rendered after the song ends.
+const TAIL_SECONDS: f32 = 10.0;
+// Below half an LSB of 16-bit output.
+const TAIL_SILENCE: f32 = 0.5 / i16::MAX as f32;
+
+// With `honour_mute_solo` unset, every channel is rendered regardless of mute / solo.
+pub fn render_to_wav(
+    path: &str,
+    state: &SharedState,
+    honour_mute_solo: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let sample_rate = 44100.0;
 
-    // Clone state for offline rendering
+    // The renderer drives its own engine with a copy of the song
     let mut render_state = state.clone();
+    if !honour_mute_solo {
+        render_state.clear_mute_solo();
+    }
 
     // Recalculate timing for the target sample rate
-    let samples_per_tick = ((sample_rate * 60.0) / (render_state.bpm * 4.0)) as usize;
-    render_state.samples_per_tick = samples_per_tick;
+    render_state.set_sample_rate(sample_rate);
+
+    // Play the whole song once: loops and live pattern launches only shape playback
+    render_state.loop_rows = None;
+    render_state.loop_orders = None;
+    render_state.stop_after_row = None;
+    render_state.queued_pattern = None;
+    render_state.pattern_launch = PatternLaunch::default();
 
-    // Reset state for rendering
-    render_state.current_row = 0;
-    render_state.current_pattern = 0;
-    render_state.current_tick_samples = samples_per_tick; // Start immediately
-    render_state.is_playing = true;
-    render_state.preview_request = None; // Ensure no stray preview notes
+    // Play the song from the start
+    render_state.play_mode = PlayMode::Song;
+    let first_pattern = render_state.order[0];
+    render_state.play_at(0, first_pattern, 0);
 
-    // Create engine
-    let state_arc = Arc::new(Mutex::new(render_state.clone()));
-    let mut engine = TrackerEngine::new(sample_rate, state_arc.clone());
+    // Create engine, without a UI to send it commands
+    let (mut engine, _handle) = TrackerEngine::new(sample_rate, render_state);
 
-    // Calculate total samples based on all patterns
-    let total_rows = render_state.patterns.len() * ROWS_PER_PATTERN;
-    let total_samples = total_rows * samples_per_tick;
+    // The song ends when playback would revisit a row (end of song or a backwards jump),
+    // so pattern breaks, jumps and tempo changes shape the export exactly like live playback.
+    engine.set_stop_on_loop(true);
 
     // Setup WAV writer
     let spec = hound::WavSpec {
@@ -40,28 +56,37 @@ pub fn render_to_wav(path: &str, state: &SharedState) -> Result<(), Box<dyn std:
 
     let mut writer = hound::WavWriter::create(path, spec)?;
 
-    // Render loop
+    // Render loop; rows start on their exact sample, so the block size does not affect timing
     let block_size = 512;
     let mut buffer = vec![0.0; block_size * 2];
 
     let mut samples_rendered = 0;
+    // Once the song has ended, send effect tails ring out until silent or for TAIL_SECONDS
+    let mut tail_remaining = (TAIL_SECONDS * sample_rate) as usize;
+
+    loop {
+        let is_playing = engine.is_playing();
+        if !is_playing {
+            if tail_remaining == 0 {
+                break;
+            }
+            tail_remaining = tail_remaining.saturating_sub(block_size);
+        }
 
-    while samples_rendered < total_samples {
-        let remaining = total_samples - samples_rendered;
-        let current_block_size = block_size.min(remaining);
-
-        let mono_slice = &mut buffer[0..current_block_size];
+        FrameProcessor::<Stereo>::process(&mut engine, &mut buffer, samples_rendered as u64);
 
-        FrameProcessor::<Mono>::process(&mut engine, mono_slice, samples_rendered as u64);
+        if !is_playing && buffer.iter().all(|s| s.abs() < TAIL_SILENCE) {
+            break;
+        }
 
-        for &sample in mono_slice.iter() {
+        // Interleaved left / right, as WAV expects
+        for &sample in buffer.iter() {
             let amplitude = i16::MAX as f32;
             let val = (sample.clamp(-1.0, 1.0) * amplitude) as i16;
             writer.write_sample(val)?;
-            writer.write_sample(val)?;
         }
 
-        samples_rendered += current_block_size;
+        samples_rendered += block_size;
     }
 
     writer.finalize()?;
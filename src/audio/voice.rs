@@ -6,10 +6,17 @@ use infinitedsp_core::core::parameter::Parameter;
 use infinitedsp_core::effects::filter::ladder_filter::LadderFilter;
 use infinitedsp_core::effects::utility::dc_source::DcSource;
 use infinitedsp_core::effects::utility::gain::Gain;
+use infinitedsp_core::effects::utility::offset::Offset;
+use infinitedsp_core::effects::utility::passthrough::Passthrough;
 use infinitedsp_core::synthesis::envelope::{Adsr, Trigger};
 use infinitedsp_core::synthesis::oscillator::{Oscillator, Waveform};
 
-use crate::core::{Instrument, ModuleConfig, WaveformType};
+use super::engine::MAX_BLOCK_FRAMES;
+use crate::core::channel::MAX_VOICES;
+use crate::core::instrument::{LfoTarget, LfoWaveform, MAX_UNISON};
+use crate::core::{Instrument, MAX_CHANNELS, ModuleConfig, NUM_INSTRUMENTS, WaveformType};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 struct RuntimeModule {
     processor: Box<dyn FrameProcessor<Mono> + Send>,
@@ -17,37 +24,300 @@ struct RuntimeModule {
     config_type: ModuleConfig,
 }
 
-pub struct SynthVoice {
+// The copies of one oscillator module, mixed at `level` into the signal that reaches it.
+struct UnisonOscillator {
+    oscillators: Vec<Oscillator>,
+    level: Parameter,
+    // Keeps the unison as loud as a single copy when the copies drift apart
+    copy_gain: f32,
+    // Sized for a whole engine block up front
+    mix_buffer: Vec<f32>,
+    copy_buffer: Vec<f32>,
+}
+
+impl UnisonOscillator {
+    fn new(oscillators: Vec<Oscillator>, level: Parameter) -> Self {
+        Self {
+            copy_gain: 1.0 / (oscillators.len() as f32).sqrt(),
+            oscillators,
+            level,
+            mix_buffer: vec![0.0; MAX_BLOCK_FRAMES],
+            copy_buffer: vec![0.0; MAX_BLOCK_FRAMES],
+        }
+    }
+}
+
+impl FrameProcessor<Mono> for UnisonOscillator {
+    fn process(&mut self, buffer: &mut [f32], sample_index: u64) {
+        let gain = self.level.get() * self.copy_gain;
+        // Longer buffers than a block, as when rendering, are mixed a block at a time
+        for (i, chunk) in buffer.chunks_mut(MAX_BLOCK_FRAMES).enumerate() {
+            let len = chunk.len();
+            let chunk_index = sample_index + (i * MAX_BLOCK_FRAMES) as u64;
+            let mix = &mut self.mix_buffer[..len];
+            let copy = &mut self.copy_buffer[..len];
+            mix.fill(0.0);
+
+            for osc in &mut self.oscillators {
+                FrameProcessor::<Mono>::process(osc, copy, chunk_index);
+                for (out, sample) in mix.iter_mut().zip(copy.iter()) {
+                    *out += sample;
+                }
+            }
+
+            for (out, sample) in chunk.iter_mut().zip(mix.iter()) {
+                *out += sample * gain;
+            }
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        for osc in &mut self.oscillators {
+            FrameProcessor::<Mono>::set_sample_rate(osc, sample_rate);
+        }
+    }
+
+    fn name(&self) -> &str {
+        "UnisonOscillator"
+    }
+}
+
+// An LFO module's settings as its targets see them, with live rate and depth.
+struct LfoRoute {
+    waveform: LfoWaveform,
+    target: LfoTarget,
+    retrigger: bool,
+    rate: Parameter,
+    depth: Parameter,
+}
+
+// One LFO as a multiplier of the parameter it modulates. Every parameter it reaches gets its
+// own copy; the copies share rate and depth and restart together, so they stay in phase.
+struct LfoSignal {
+    waveform: LfoWaveform,
+    target: LfoTarget,
+    rate: Parameter,
+    depth: Parameter,
+    restart: Arc<AtomicBool>,
+    phase: f32,
+    rng_state: u32,
+    held: f32,
+    sample_rate: f32,
+}
+
+impl LfoSignal {
+    fn new(route: &LfoRoute, restart: Arc<AtomicBool>, sample_rate: f32) -> Self {
+        Self {
+            waveform: route.waveform,
+            target: route.target,
+            rate: route.rate.clone(),
+            depth: route.depth.clone(),
+            restart,
+            phase: 0.0,
+            rng_state: 12345,
+            held: 0.0,
+            sample_rate,
+        }
+    }
+
+    fn next_random(&mut self) -> f32 {
+        self.rng_state = self.rng_state.wrapping_mul(1103515245).wrapping_add(12345);
+        ((self.rng_state >> 16) & 0x7FFF) as f32 / 16384.0 - 1.0
+    }
+}
+
+impl FrameProcessor<Mono> for LfoSignal {
+    fn process(&mut self, buffer: &mut [f32], _sample_index: u64) {
+        if self.restart.swap(false, Ordering::Relaxed) {
+            self.phase = 0.0;
+            self.held = self.next_random();
+        }
+        let increment = self.rate.get() / self.sample_rate;
+        let depth = self.depth.get().clamp(0.0, 1.0);
+
+        for sample in buffer.iter_mut() {
+            // Bipolar, -1.0..=1.0
+            let value = match self.waveform {
+                LfoWaveform::Sine => (self.phase * std::f32::consts::TAU).sin(),
+                LfoWaveform::Triangle => 4.0 * ((self.phase + 0.75).fract() - 0.5).abs() - 1.0,
+                LfoWaveform::Saw => 2.0 * self.phase - 1.0,
+                LfoWaveform::Square => {
+                    if self.phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                LfoWaveform::Random => self.held,
+            };
+            *sample = match self.target {
+                LfoTarget::Pitch => 2.0f32.powf(value * depth),
+                LfoTarget::Cutoff => 2.0f32.powf(value * depth * 4.0),
+                LfoTarget::Gain => 1.0 - depth * (0.5 - 0.5 * value),
+            };
+
+            self.phase += increment;
+            if self.phase >= 1.0 {
+                self.phase = self.phase.fract();
+                self.held = self.next_random();
+            }
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    fn name(&self) -> &str {
+        "LfoSignal"
+    }
+}
+
+// The processors of one voice, built for one instrument's modules on the UI thread. Voices take
+// them from the `VoiceBank` and hand them back once silent.
+pub struct VoiceGraph {
+    instrument: usize,
+    // Pool generation the graph was built for, older graphs are not reused
+    generation: u64,
     modules: Vec<RuntimeModule>,
     triggers: Vec<Trigger>,
+    // Restart flags of the LFO copies that start over on every note
+    lfo_restarts: Vec<Arc<AtomicBool>>,
     pitch: Parameter,
     gate: Parameter,
+    velocity: Parameter,
+    velocity_gain: Gain,
+    // Song tempo, for tempo-synced LFOs
+    bpm: f32,
     sample_rate: f32,
 }
 
-impl SynthVoice {
-    pub fn new(sample_rate: f32) -> Self {
-        Self {
+impl VoiceGraph {
+    pub fn new(
+        instrument_idx: usize,
+        generation: u64,
+        instrument: &Instrument,
+        bpm: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let velocity = Parameter::new(1.0);
+        let mut velocity_gain = Gain::new(AudioParam::Linked(velocity.clone()));
+        FrameProcessor::<Mono>::set_sample_rate(&mut velocity_gain, sample_rate);
+
+        let mut graph = Self {
+            instrument: instrument_idx,
+            generation,
             modules: Vec::new(),
             triggers: Vec::new(),
+            lfo_restarts: Vec::new(),
             pitch: Parameter::new(440.0),
             gate: Parameter::new(0.0),
+            velocity,
+            velocity_gain,
+            bpm,
             sample_rate,
+        };
+        graph.build(instrument);
+        graph
+    }
+
+    fn velocity_cutoff(cutoff: f32, velocity_amount: f32, velocity: f32) -> f32 {
+        let amount = velocity_amount.clamp(0.0, 1.0);
+        (cutoff * (1.0 - amount * (1.0 - velocity))).max(20.0)
+    }
+
+    // Frequency ratio of an oscillator tuned `semitones` plus `cents` away from the note.
+    fn pitch_ratio(semitones: f32, cents: f32) -> f32 {
+        2.0f32.powf((semitones + cents / 100.0) / 12.0)
+    }
+
+    // Detune of one unison copy in cents, the copies spread evenly around the note.
+    fn unison_cents(spread: f32, copy: usize, copies: usize) -> f32 {
+        if copies < 2 {
+            0.0
+        } else {
+            spread * (copy as f32 / (copies - 1) as f32 - 0.5)
         }
     }
 
-    pub fn build(&mut self, instrument: &Instrument) {
-        self.modules.clear();
-        self.triggers.clear();
+    // Cycles per second of an LFO; a synced one spans `sync_rows` rows at 4 rows per beat.
+    fn lfo_rate(rate: f32, sync_rows: usize, bpm: f32) -> f32 {
+        if sync_rows == 0 {
+            rate
+        } else {
+            bpm * 4.0 / (60.0 * sync_rows as f32)
+        }
+    }
+
+    // Multiplies a parameter signal by every LFO routed to `target`.
+    fn modulate(
+        &mut self,
+        mut signal: DspChain<Mono>,
+        lfos: &[LfoRoute],
+        target: LfoTarget,
+    ) -> DspChain<Mono> {
+        for route in lfos.iter().filter(|route| route.target == target) {
+            let restart = Arc::new(AtomicBool::new(false));
+            if route.retrigger {
+                self.lfo_restarts.push(restart.clone());
+            }
+            let lfo = LfoSignal::new(route, restart, self.sample_rate);
+            signal = signal.and(Gain::new(AudioParam::Dynamic(Box::new(lfo))));
+        }
+        signal
+    }
+
+    // A parameter at `value`, moved by the LFOs routed to `target` if there are any.
+    fn modulated_param(
+        &mut self,
+        value: &Parameter,
+        lfos: &[LfoRoute],
+        target: LfoTarget,
+    ) -> AudioParam {
+        if !lfos.iter().any(|route| route.target == target) {
+            return AudioParam::Linked(value.clone());
+        }
+        let source = DcSource::new(AudioParam::Linked(value.clone()));
+        let signal = DspChain::new(source, self.sample_rate);
+        AudioParam::Dynamic(Box::new(self.modulate(signal, lfos, target)))
+    }
+
+    fn build(&mut self, instrument: &Instrument) {
+        // LFOs reach modules before and after them, so they are set up first
+        let lfos: Vec<LfoRoute> = instrument
+            .modules
+            .iter()
+            .filter_map(|config| match config {
+                ModuleConfig::Lfo {
+                    waveform,
+                    target,
+                    rate,
+                    sync_rows,
+                    depth,
+                    retrigger,
+                } => Some(LfoRoute {
+                    waveform: *waveform,
+                    target: *target,
+                    retrigger: *retrigger,
+                    rate: Parameter::new(Self::lfo_rate(*rate, *sync_rows, self.bpm)),
+                    depth: Parameter::new(*depth),
+                }),
+                _ => None,
+            })
+            .collect();
+        let mut lfo_routes = lfos.iter();
 
         for config in &instrument.modules {
             match config {
                 ModuleConfig::Oscillator {
                     waveform,
-                    pitch_offset: _,
-                    detune: _,
+                    pitch_offset,
+                    detune,
                     pitch_env_amount,
                     pitch_env_decay,
+                    level,
+                    unison,
+                    unison_spread,
                 } => {
                     let wave = match waveform {
                         WaveformType::Sine => Waveform::Sine,
@@ -56,60 +326,74 @@ impl SynthVoice {
                         WaveformType::Triangle => Waveform::Triangle,
                         WaveformType::Noise => Waveform::WhiteNoise,
                     };
+                    let copies = (*unison).clamp(1, MAX_UNISON);
+                    let pitch_env = (*pitch_env_amount > 0.0).then(|| {
+                        (
+                            Parameter::new(*pitch_env_amount),
+                            Parameter::new(*pitch_env_decay),
+                        )
+                    });
 
-                    let mut osc = if *pitch_env_amount > 0.0 {
-                        let p_pe_amount = Parameter::new(*pitch_env_amount);
-                        let p_pe_decay = Parameter::new(*pitch_env_decay);
-
+                    let p_level = Parameter::new(*level);
+                    let mut params = vec![p_level.clone()];
+                    let mut oscillators = Vec::with_capacity(copies);
+                    for copy in 0..copies {
+                        // Note frequency scaled by the pitch offset, the pitch envelope adds on top
+                        let cents = detune + Self::unison_cents(*unison_spread, copy, copies);
+                        let p_ratio = Parameter::new(Self::pitch_ratio(*pitch_offset, cents));
                         let base_pitch = DcSource::new(AudioParam::Linked(self.pitch.clone()));
+                        let ratio_gain = Gain::new(AudioParam::Linked(p_ratio.clone()));
+                        let freq = DspChain::new(base_pitch, self.sample_rate).and(ratio_gain);
+                        let mut freq = self.modulate(freq, &lfos, LfoTarget::Pitch);
+
+                        if let Some((p_pe_amount, p_pe_decay)) = &pitch_env {
+                            let pe_env = Adsr::new(
+                                AudioParam::Linked(self.gate.clone()),
+                                AudioParam::Static(0.001),
+                                AudioParam::Linked(p_pe_decay.clone()),
+                                AudioParam::Static(0.0),
+                                AudioParam::Static(0.01),
+                            );
+                            self.triggers.push(pe_env.create_trigger());
+
+                            let pe_gain = Gain::new(AudioParam::Linked(p_pe_amount.clone()));
+
+                            let env_chain = DspChain::new(pe_env, self.sample_rate).and(pe_gain);
+                            let env_offset =
+                                Offset::new_param(AudioParam::Dynamic(Box::new(env_chain)));
+                            freq = freq.and(env_offset);
+                        }
 
-                        let pe_env = Adsr::new(
-                            AudioParam::Linked(self.gate.clone()),
-                            AudioParam::Static(0.001),
-                            AudioParam::Linked(p_pe_decay.clone()),
-                            AudioParam::Static(0.0),
-                            AudioParam::Static(0.01),
-                        );
-                        self.triggers.push(pe_env.create_trigger());
-
-                        let pe_gain = Gain::new(AudioParam::Linked(p_pe_amount.clone()));
-
-                        let env_chain = DspChain::new(pe_env, self.sample_rate).and(pe_gain);
-
-                        let freq_mod =
-                            DspChain::new(base_pitch, self.sample_rate).and_mix(1.0, env_chain);
-
-                        let osc = Oscillator::new(AudioParam::Dynamic(Box::new(freq_mod)), wave);
-
-                        let mut osc_box = Box::new(osc);
-                        osc_box.set_sample_rate(self.sample_rate);
-
-                        self.modules.push(RuntimeModule {
-                            processor: osc_box,
-                            params: vec![p_pe_amount, p_pe_decay],
-                            config_type: config.clone(),
-                        });
-                        continue;
-                    } else {
-                        Oscillator::new(AudioParam::Linked(self.pitch.clone()), wave)
-                    };
-
-                    osc.set_sample_rate(self.sample_rate);
+                        let mut osc = Oscillator::new(AudioParam::Dynamic(Box::new(freq)), wave);
+                        FrameProcessor::<Mono>::set_sample_rate(&mut osc, self.sample_rate);
+                        oscillators.push(osc);
+                        params.push(p_ratio);
+                    }
+                    if let Some((p_pe_amount, p_pe_decay)) = pitch_env {
+                        params.push(p_pe_amount);
+                        params.push(p_pe_decay);
+                    }
 
                     self.modules.push(RuntimeModule {
-                        processor: Box::new(osc),
-                        params: vec![],
+                        processor: Box::new(UnisonOscillator::new(oscillators, p_level)),
+                        params,
                         config_type: config.clone(),
                     });
                 }
-                ModuleConfig::Filter { cutoff, resonance } => {
-                    let p_cutoff = Parameter::new(*cutoff);
+                ModuleConfig::Filter {
+                    cutoff,
+                    resonance,
+                    velocity_amount,
+                } => {
+                    let p_cutoff = Parameter::new(Self::velocity_cutoff(
+                        *cutoff,
+                        *velocity_amount,
+                        self.velocity.get(),
+                    ));
                     let p_res = Parameter::new(*resonance);
 
-                    let mut filter = LadderFilter::new(
-                        AudioParam::Linked(p_cutoff.clone()),
-                        AudioParam::Linked(p_res.clone()),
-                    );
+                    let cutoff = self.modulated_param(&p_cutoff, &lfos, LfoTarget::Cutoff);
+                    let mut filter = LadderFilter::new(cutoff, AudioParam::Linked(p_res.clone()));
                     filter.set_sample_rate(self.sample_rate);
 
                     self.modules.push(RuntimeModule {
@@ -149,7 +433,8 @@ impl SynthVoice {
                 }
                 ModuleConfig::Gain { level } => {
                     let p_level = Parameter::new(*level);
-                    let mut gain = Gain::new(AudioParam::Linked(p_level.clone()));
+                    let level = self.modulated_param(&p_level, &lfos, LfoTarget::Gain);
+                    let mut gain = Gain::new(level);
                     FrameProcessor::<Mono>::set_sample_rate(&mut gain, self.sample_rate);
 
                     self.modules.push(RuntimeModule {
@@ -158,111 +443,149 @@ impl SynthVoice {
                         config_type: config.clone(),
                     });
                 }
+                // The LFO only modulates the modules it is routed to, the signal passes through
+                ModuleConfig::Lfo { .. } => {
+                    let params = match lfo_routes.next() {
+                        Some(route) => vec![route.rate.clone(), route.depth.clone()],
+                        None => vec![],
+                    };
+                    self.modules.push(RuntimeModule {
+                        processor: Box::new(Passthrough::new()),
+                        params,
+                        config_type: config.clone(),
+                    });
+                }
             }
         }
     }
 
-    pub fn update_params(&mut self, freq: f32, instrument: Option<&Instrument>) {
-        self.pitch.set(freq);
-
-        if let Some(inst) = instrument {
-            let needs_rebuild = if self.modules.len() != inst.modules.len() {
-                true
-            } else {
-                self.modules.iter().zip(&inst.modules).any(|(rt, cfg)| {
-                    match (&rt.config_type, cfg) {
-                        (
-                            ModuleConfig::Oscillator {
-                                waveform: w1,
-                                pitch_env_amount: a1,
-                                ..
-                            },
-                            ModuleConfig::Oscillator {
-                                waveform: w2,
-                                pitch_env_amount: a2,
-                                ..
-                            },
-                        ) => w1 != w2 || (*a1 > 0.0) != (*a2 > 0.0),
-                        (ModuleConfig::Filter { .. }, ModuleConfig::Filter { .. }) => false,
-                        (ModuleConfig::Adsr { .. }, ModuleConfig::Adsr { .. }) => false,
-                        (ModuleConfig::Gain { .. }, ModuleConfig::Gain { .. }) => false,
-                        _ => true,
-                    }
-                })
-            };
-
-            if needs_rebuild {
-                self.build(inst);
-            } else {
-                for (rt, cfg) in self.modules.iter_mut().zip(&inst.modules) {
-                    match (cfg, &rt.params) {
-                        (
-                            ModuleConfig::Oscillator {
-                                pitch_env_amount,
-                                pitch_env_decay,
-                                ..
-                            },
-                            params,
-                        ) => {
-                            if !params.is_empty() {
-                                params[0].set(*pitch_env_amount);
-                                params[1].set(*pitch_env_decay);
-                            }
-                        }
-                        (ModuleConfig::Filter { cutoff, resonance }, params) => {
-                            if params.len() >= 2 {
-                                params[0].set(*cutoff);
-                                params[1].set(*resonance);
-                            }
-                        }
-                        (
-                            ModuleConfig::Adsr {
-                                attack,
-                                decay,
-                                sustain,
-                                release,
-                            },
-                            params,
-                        ) => {
-                            if params.len() >= 4 {
-                                params[0].set(*attack);
-                                params[1].set(*decay);
-                                params[2].set(*sustain);
-                                params[3].set(*release);
-                            }
-                        }
-                        (ModuleConfig::Gain { level }, params) => {
-                            if !params.is_empty() {
-                                params[0].set(*level);
-                            }
+    // Sets the parameters of an instrument with the layout the graph was built for.
+    fn set_params(&mut self, instrument: &Instrument, velocity: f32) {
+        let bpm = self.bpm;
+        for (rt, cfg) in self.modules.iter_mut().zip(&instrument.modules) {
+            match (cfg, &rt.params) {
+                (
+                    ModuleConfig::Oscillator {
+                        pitch_offset,
+                        detune,
+                        pitch_env_amount,
+                        pitch_env_decay,
+                        level,
+                        unison,
+                        unison_spread,
+                        ..
+                    },
+                    params,
+                ) => {
+                    // Level, a frequency ratio per unison copy, then the pitch envelope
+                    let copies = (*unison).clamp(1, MAX_UNISON);
+                    if params.len() > copies {
+                        params[0].set(*level);
+                        for (copy, ratio) in params[1..=copies].iter().enumerate() {
+                            let cents = detune + Self::unison_cents(*unison_spread, copy, copies);
+                            ratio.set(Self::pitch_ratio(*pitch_offset, cents));
                         }
                     }
+                    if params.len() >= copies + 3 {
+                        params[copies + 1].set(*pitch_env_amount);
+                        params[copies + 2].set(*pitch_env_decay);
+                    }
+                }
+                (
+                    ModuleConfig::Filter {
+                        cutoff,
+                        resonance,
+                        velocity_amount,
+                    },
+                    params,
+                ) => {
+                    if params.len() >= 2 {
+                        params[0].set(Self::velocity_cutoff(*cutoff, *velocity_amount, velocity));
+                        params[1].set(*resonance);
+                    }
+                }
+                (
+                    ModuleConfig::Adsr {
+                        attack,
+                        decay,
+                        sustain,
+                        release,
+                    },
+                    params,
+                ) => {
+                    if params.len() >= 4 {
+                        params[0].set(*attack);
+                        params[1].set(*decay);
+                        params[2].set(*sustain);
+                        params[3].set(*release);
+                    }
+                }
+                (ModuleConfig::Gain { level }, params) => {
+                    if !params.is_empty() {
+                        params[0].set(*level);
+                    }
+                }
+                (
+                    ModuleConfig::Lfo {
+                        rate,
+                        sync_rows,
+                        depth,
+                        ..
+                    },
+                    params,
+                ) => {
+                    if params.len() >= 2 {
+                        params[0].set(Self::lfo_rate(*rate, *sync_rows, bpm));
+                        params[1].set(*depth);
+                    }
                 }
             }
+            // Kept current for tempo changes
+            rt.config_type.clone_from(cfg);
         }
     }
 
-    pub fn trigger(&mut self) {
+    fn set_bpm(&mut self, bpm: f32) {
+        if bpm == self.bpm {
+            return;
+        }
+        self.bpm = bpm;
+        for rt in &self.modules {
+            if let ModuleConfig::Lfo {
+                rate, sync_rows, ..
+            } = rt.config_type
+                && sync_rows > 0
+                && !rt.params.is_empty()
+            {
+                rt.params[0].set(Self::lfo_rate(rate, sync_rows, bpm));
+            }
+        }
+    }
+
+    fn trigger(&mut self) {
         self.gate.set(1.0);
         for trigger in &self.triggers {
             trigger.fire();
         }
-    }
-
-    pub fn release(&mut self) {
-        self.gate.set(0.0);
+        for restart in &self.lfo_restarts {
+            restart.store(true, Ordering::Relaxed);
+        }
     }
 }
 
-impl FrameProcessor<Mono> for SynthVoice {
+impl FrameProcessor<Mono> for VoiceGraph {
     fn process(&mut self, buffer: &mut [f32], sample_index: u64) {
+        // Oscillators add to the signal, starting from silence
+        buffer.fill(0.0);
         for module in &mut self.modules {
             module.processor.process(buffer, sample_index);
         }
+        FrameProcessor::<Mono>::process(&mut self.velocity_gain, buffer, sample_index);
     }
 
     fn set_sample_rate(&mut self, sample_rate: f32) {
         self.sample_rate = sample_rate;
+        FrameProcessor::<Mono>::set_sample_rate(&mut self.velocity_gain, sample_rate);
         for module in &mut self.modules {
             module.processor.set_sample_rate(sample_rate);
         }
@@ -276,7 +599,7 @@ impl FrameProcessor<Mono> for SynthVoice {
     }
 
     fn name(&self) -> &str {
-        "SynthVoice"
+        "VoiceGraph"
     }
 
     fn visualize(&self, indent: usize) -> String {
@@ -288,3 +611,291 @@ impl FrameProcessor<Mono> for SynthVoice {
         output
     }
 }
+
+// Whether a graph built for `built` can play `modules` by only setting parameters.
+pub fn same_layout(built: &[ModuleConfig], modules: &[ModuleConfig]) -> bool {
+    built.len() == modules.len()
+        && built.iter().zip(modules).all(|(a, b)| match (a, b) {
+            (
+                ModuleConfig::Oscillator {
+                    waveform: w1,
+                    pitch_env_amount: a1,
+                    unison: u1,
+                    ..
+                },
+                ModuleConfig::Oscillator {
+                    waveform: w2,
+                    pitch_env_amount: a2,
+                    unison: u2,
+                    ..
+                },
+            ) => w1 == w2 && (*a1 > 0.0) == (*a2 > 0.0) && u1 == u2,
+            (ModuleConfig::Filter { .. }, ModuleConfig::Filter { .. }) => true,
+            (ModuleConfig::Adsr { .. }, ModuleConfig::Adsr { .. }) => true,
+            (ModuleConfig::Gain { .. }, ModuleConfig::Gain { .. }) => true,
+            (
+                ModuleConfig::Lfo {
+                    waveform: w1,
+                    target: t1,
+                    retrigger: r1,
+                    ..
+                },
+                ModuleConfig::Lfo {
+                    waveform: w2,
+                    target: t2,
+                    retrigger: r2,
+                    ..
+                },
+            ) => w1 == w2 && t1 == t2 && r1 == r2,
+            _ => false,
+        })
+}
+
+// Spare graphs for one instrument, all built for the same modules.
+pub struct VoicePool {
+    generation: u64,
+    graphs: Vec<VoiceGraph>,
+}
+
+impl VoicePool {
+    pub fn new(
+        instrument_idx: usize,
+        generation: u64,
+        instrument: &Instrument,
+        voices: usize,
+        bpm: f32,
+        sample_rate: f32,
+    ) -> Self {
+        let graphs = (0..voices)
+            .map(|_| VoiceGraph::new(instrument_idx, generation, instrument, bpm, sample_rate))
+            .collect();
+        Self { generation, graphs }
+    }
+}
+
+// Graphs replaced by a newer pool can stay in use this long before they go back to the UI.
+pub const MAX_RETIRED: usize = MAX_CHANNELS * MAX_VOICES;
+
+// The voice graphs of every instrument, owned by the engine. Voices take one when a note starts
+// and give it back once silent, so playing notes never builds processors on the audio thread.
+pub struct VoiceBank {
+    pools: Vec<VoicePool>,
+    // Graphs of replaced pools, waiting for the next song update to carry them off
+    retired: Vec<VoiceGraph>,
+}
+
+impl VoiceBank {
+    pub fn new() -> Self {
+        Self {
+            pools: (0..NUM_INSTRUMENTS)
+                .map(|_| VoicePool {
+                    generation: 0,
+                    graphs: Vec::new(),
+                })
+                .collect(),
+            retired: Vec::with_capacity(MAX_RETIRED),
+        }
+    }
+
+    fn is_current(&self, graph: &VoiceGraph) -> bool {
+        self.pools
+            .get(graph.instrument)
+            .is_some_and(|pool| pool.generation == graph.generation)
+    }
+
+    fn take(&mut self, instrument_idx: usize) -> Option<VoiceGraph> {
+        self.pools.get_mut(instrument_idx)?.graphs.pop()
+    }
+
+    // Puts a graph back, or returns it when there is no room to keep it without allocating.
+    fn give_back(&mut self, graph: VoiceGraph) -> Option<VoiceGraph> {
+        let graphs = if self.is_current(&graph) {
+            &mut self.pools[graph.instrument].graphs
+        } else {
+            &mut self.retired
+        };
+        if graphs.len() < graphs.capacity() {
+            graphs.push(graph);
+            None
+        } else {
+            Some(graph)
+        }
+    }
+
+    // Swaps in the pools of a song update, leaving the replaced ones and their spare graphs in
+    // the update. Graphs of theirs that voices still hold are retired once given back.
+    pub fn swap_pools(&mut self, pools: &mut [Option<VoicePool>]) {
+        for (current, new) in self.pools.iter_mut().zip(pools) {
+            if let Some(new) = new {
+                std::mem::swap(current, new);
+            }
+        }
+    }
+
+    // Swaps an empty list in for the retired graphs, which go back with the update.
+    pub fn swap_retired(&mut self, retired: &mut Vec<VoiceGraph>) {
+        std::mem::swap(&mut self.retired, retired);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let pooled = self
+            .pools
+            .iter_mut()
+            .flat_map(|pool| pool.graphs.iter_mut());
+        for graph in pooled.chain(self.retired.iter_mut()) {
+            FrameProcessor::<Mono>::set_sample_rate(graph, sample_rate);
+        }
+    }
+}
+
+impl Default for VoiceBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One voice of a channel. It plays through a graph from the bank, and is silent without one.
+pub struct SynthVoice {
+    graph: Option<VoiceGraph>,
+    // Song tempo, handed to each graph the voice takes
+    bpm: f32,
+}
+
+impl SynthVoice {
+    pub fn new() -> Self {
+        Self {
+            graph: None,
+            bpm: 0.0,
+        }
+    }
+
+    // Makes sure the voice holds a current graph for the instrument, keeping the old one when the
+    // pool has run dry.
+    fn take_graph(&mut self, instrument_idx: usize, bank: &mut VoiceBank) {
+        if self
+            .graph
+            .as_ref()
+            .is_some_and(|graph| graph.instrument == instrument_idx && bank.is_current(graph))
+        {
+            return;
+        }
+        let Some(mut graph) = bank.take(instrument_idx) else {
+            return;
+        };
+        if let Some(old) = self.graph.take() {
+            // A legato or gliding note carries on from where the old graph was
+            graph.gate.set(old.gate.get());
+            graph.pitch.set(old.pitch.get());
+            if let Some(old) = bank.give_back(old) {
+                bank.pools[instrument_idx].graphs.push(graph);
+                self.graph = Some(old);
+                return;
+            }
+        }
+        graph.set_bpm(self.bpm);
+        self.graph = Some(graph);
+    }
+
+    pub fn update_params(
+        &mut self,
+        freq: f32,
+        velocity: f32,
+        instrument_idx: usize,
+        instrument: &Instrument,
+        bank: &mut VoiceBank,
+    ) {
+        self.take_graph(instrument_idx, bank);
+        if let Some(graph) = &mut self.graph {
+            graph.pitch.set(freq);
+            graph.velocity.set(velocity);
+            if graph.instrument == instrument_idx && bank.is_current(graph) {
+                graph.set_params(instrument, velocity);
+            }
+        }
+    }
+
+    // Gives the graph back to the bank, called once the voice has gone silent.
+    pub fn return_graph(&mut self, bank: &mut VoiceBank) {
+        if let Some(graph) = self.graph.take()
+            && let Some(graph) = bank.give_back(graph)
+        {
+            self.graph = Some(graph);
+        }
+    }
+
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm;
+        if let Some(graph) = &mut self.graph {
+            graph.set_bpm(bpm);
+        }
+    }
+
+    pub fn set_pitch(&mut self, freq: f32) {
+        if let Some(graph) = &mut self.graph {
+            graph.pitch.set(freq);
+        }
+    }
+
+    pub fn trigger(&mut self) {
+        if let Some(graph) = &mut self.graph {
+            graph.trigger();
+        }
+    }
+
+    pub fn release(&mut self) {
+        if let Some(graph) = &mut self.graph {
+            graph.gate.set(0.0);
+        }
+    }
+
+    pub fn is_gate_open(&self) -> bool {
+        self.graph
+            .as_ref()
+            .is_some_and(|graph| graph.gate.get() > 0.0)
+    }
+
+    // Silence immediately instead of running the release stage.
+    pub fn cut(&mut self) {
+        if let Some(graph) = &mut self.graph {
+            graph.gate.set(0.0);
+            graph.velocity.set(0.0);
+        }
+    }
+}
+
+impl Default for SynthVoice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameProcessor<Mono> for SynthVoice {
+    fn process(&mut self, buffer: &mut [f32], sample_index: u64) {
+        match &mut self.graph {
+            Some(graph) => FrameProcessor::<Mono>::process(graph, buffer, sample_index),
+            None => buffer.fill(0.0),
+        }
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        if let Some(graph) = &mut self.graph {
+            FrameProcessor::<Mono>::set_sample_rate(graph, sample_rate);
+        }
+    }
+
+    fn latency_samples(&self) -> u32 {
+        self.graph
+            .as_ref()
+            .map_or(0, |graph| graph.latency_samples())
+    }
+
+    fn name(&self) -> &str {
+        "SynthVoice"
+    }
+
+    fn visualize(&self, indent: usize) -> String {
+        self.graph
+            .as_ref()
+            .map_or_else(String::new, |graph| graph.visualize(indent))
+    }
+}
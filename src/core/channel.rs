@@ -0,0 +1,104 @@
+use super::insert::InsertConfig;
+use serde::{Deserialize, Serialize};
+
+// Voices per channel, used by Poly voice mode to let release tails overlap.
+pub const DEFAULT_VOICE_LIMIT: usize = 4;
+pub const MAX_VOICES: usize = 16;
+// Highest fader / master volume (+6 dB).
+pub const MAX_FADER_GAIN: f32 = 2.0;
+
+// How a channel handles a new note while another one is still sounding.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum VoiceMode {
+    // Restart the envelope on every note (drums)
+    Retrigger,
+    // Keep the envelope running when the same key is repeated
+    #[default]
+    Legato,
+    // Keep the envelope running and slide to every new key
+    Glide,
+    // Let the previous note ring out while the new one starts
+    Poly,
+}
+
+impl VoiceMode {
+    pub fn next(self) -> Self {
+        match self {
+            VoiceMode::Retrigger => VoiceMode::Legato,
+            VoiceMode::Legato => VoiceMode::Glide,
+            VoiceMode::Glide => VoiceMode::Poly,
+            VoiceMode::Poly => VoiceMode::Retrigger,
+        }
+    }
+
+    pub fn short_name(self) -> &'static str {
+        match self {
+            VoiceMode::Retrigger => "Trg",
+            VoiceMode::Legato => "Leg",
+            VoiceMode::Glide => "Gld",
+            VoiceMode::Poly => "Pol",
+        }
+    }
+}
+
+// Which voice a Poly channel takes over when all of its voices are busy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum VoiceStealing {
+    #[default]
+    Oldest,
+    Quietest,
+}
+
+// Per-channel mixer settings, independent of the pattern data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelSettings {
+    #[serde(default)]
+    pub muted: bool,
+    #[serde(default)]
+    pub solo: bool,
+    #[serde(default)]
+    pub voice_mode: VoiceMode,
+    #[serde(default = "default_voice_limit")]
+    pub voice_limit: usize,
+    #[serde(default)]
+    pub voice_stealing: VoiceStealing,
+    // -1.0 = left, 0.0 = center, 1.0 = right
+    #[serde(default)]
+    pub pan: f32,
+    // Fader gain, 1.0 = 0 dB
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    // Post-fader send amounts, 0.0..=1.0
+    #[serde(default)]
+    pub reverb_send: f32,
+    #[serde(default)]
+    pub delay_send: f32,
+    // Insert effects, applied in order before the fader
+    #[serde(default)]
+    pub inserts: Vec<InsertConfig>,
+}
+
+fn default_voice_limit() -> usize {
+    DEFAULT_VOICE_LIMIT
+}
+
+pub fn default_volume() -> f32 {
+    1.0
+}
+
+impl Default for ChannelSettings {
+    fn default() -> Self {
+        Self {
+            muted: false,
+            solo: false,
+            voice_mode: VoiceMode::default(),
+            voice_limit: DEFAULT_VOICE_LIMIT,
+            voice_stealing: VoiceStealing::default(),
+            pan: 0.0,
+            volume: default_volume(),
+            reverb_send: 0.0,
+            delay_send: 0.0,
+            inserts: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+/// Effect commands of the pattern effect column.
+///
+/// Pitch amounts are in 1/16 semitone steps per tick, vibrato depth in 1/8 semitones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// 0xy: Cycle between the note, +x and +y semitones every tick.
+    Arpeggio { x: u8, y: u8 },
+    /// 1xx: Slide pitch up.
+    SlideUp(u8),
+    /// 2xx: Slide pitch down.
+    SlideDown(u8),
+    /// 3xx: Slide towards the row's note without retriggering (00 = last speed).
+    TonePortamento(u8),
+    /// 4xy: Vibrato with speed x and depth y (0 = last value).
+    Vibrato { speed: u8, depth: u8 },
+    /// 8xx: Set channel panning (00 = left, 80 = center, FF = right).
+    SetPan(u8),
+    /// E9x: Retrigger the note every x ticks.
+    Retrigger(u8),
+    /// ECx: Cut the note after x ticks.
+    NoteCut(u8),
+    /// EDx: Delay the row's note by x ticks.
+    NoteDelay(u8),
+    /// Bxx: Jump to song position xx after this row.
+    PositionJump(u8),
+    /// Dxx: Continue at row xx of the next pattern after this row.
+    PatternBreak(u8),
+    /// Fxx (xx < 20): Set ticks per row.
+    SetTicksPerRow(u8),
+    /// Fxx (xx >= 20): Set BPM.
+    SetBpm(u8),
+}
+
+impl Effect {
+    pub fn decode(command: u8, param: u8) -> Option<Self> {
+        match command {
+            0x0 if param == 0 => None,
+            0x0 => Some(Effect::Arpeggio {
+                x: param >> 4,
+                y: param & 0x0F,
+            }),
+            0x1 => Some(Effect::SlideUp(param)),
+            0x2 => Some(Effect::SlideDown(param)),
+            0x3 => Some(Effect::TonePortamento(param)),
+            0x4 => Some(Effect::Vibrato {
+                speed: param >> 4,
+                depth: param & 0x0F,
+            }),
+            0x8 => Some(Effect::SetPan(param)),
+            0xB => Some(Effect::PositionJump(param)),
+            0xE => match param >> 4 {
+                0x9 => Some(Effect::Retrigger(param & 0x0F)),
+                0xC => Some(Effect::NoteCut(param & 0x0F)),
+                0xD => Some(Effect::NoteDelay(param & 0x0F)),
+                _ => None,
+            },
+            0xD => Some(Effect::PatternBreak(param)),
+            0xF if param == 0 => None,
+            0xF if param < 0x20 => Some(Effect::SetTicksPerRow(param)),
+            0xF => Some(Effect::SetBpm(param)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_every_command() {
+        let table = [
+            (0x0, 0x37, Some(Effect::Arpeggio { x: 3, y: 7 })),
+            (0x1, 0x20, Some(Effect::SlideUp(0x20))),
+            (0x2, 0x08, Some(Effect::SlideDown(0x08))),
+            (0x3, 0x00, Some(Effect::TonePortamento(0x00))),
+            (
+                0x4,
+                0x5A,
+                Some(Effect::Vibrato {
+                    speed: 5,
+                    depth: 0xA,
+                }),
+            ),
+            (0x8, 0x80, Some(Effect::SetPan(0x80))),
+            (0xB, 0x02, Some(Effect::PositionJump(0x02))),
+            (0xD, 0x10, Some(Effect::PatternBreak(0x10))),
+            (0xE, 0x93, Some(Effect::Retrigger(3))),
+            (0xE, 0xC2, Some(Effect::NoteCut(2))),
+            (0xE, 0xD1, Some(Effect::NoteDelay(1))),
+            (0xF, 0x01, Some(Effect::SetTicksPerRow(0x01))),
+            (0xF, 0x1F, Some(Effect::SetTicksPerRow(0x1F))),
+            (0xF, 0x20, Some(Effect::SetBpm(0x20))),
+            (0xF, 0xFF, Some(Effect::SetBpm(0xFF))),
+        ];
+        for (command, param, effect) in table {
+            assert_eq!(
+                Effect::decode(command, param),
+                effect,
+                "{:X}{:02X}",
+                command,
+                param
+            );
+        }
+    }
+
+    #[test]
+    fn empty_and_unknown_commands_decode_to_none() {
+        for (command, param) in [
+            (0x0, 0x00),
+            (0xF, 0x00),
+            (0xE, 0x10),
+            (0x5, 0x11),
+            (0xC, 0x40),
+        ] {
+            assert_eq!(
+                Effect::decode(command, param),
+                None,
+                "{:X}{:02X}",
+                command,
+                param
+            );
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+
+// Rows in a groove cycle.
+pub const GROOVE_LENGTHS: [usize; 4] = [2, 4, 8, 16];
+// Largest timing offset of a row, as a fraction of a row.
+pub const MAX_GROOVE_OFFSET: f32 = 0.5;
+
+// Row timing that repeats every few rows. Offsets are fractions of a row, positive ones play
+// late; swing delays every second row on top of the template.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Groove {
+    pub swing: f32,
+    pub offsets: Vec<f32>,
+}
+
+impl Default for Groove {
+    fn default() -> Self {
+        Self {
+            swing: 0.0,
+            offsets: vec![0.0; GROOVE_LENGTHS[0]],
+        }
+    }
+}
+
+impl Groove {
+    // How far `row` of a pattern plays from its straight position, as a fraction of a row.
+    pub fn row_offset(&self, row: usize) -> f32 {
+        let template = if self.offsets.is_empty() {
+            0.0
+        } else {
+            self.offsets[row % self.offsets.len()]
+        };
+        let swing = if row % 2 == 1 { self.swing } else { 0.0 };
+        (template + swing).clamp(-MAX_GROOVE_OFFSET, MAX_GROOVE_OFFSET)
+    }
+
+    // Lengthening repeats the template, shortening drops rows at the end.
+    pub fn set_cycle_length(&mut self, len: usize) {
+        if self.offsets.is_empty() {
+            self.offsets = vec![0.0; len];
+        } else {
+            self.offsets = (0..len)
+                .map(|i| self.offsets[i % self.offsets.len()])
+                .collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_offset_repeats_the_template() {
+        let groove = Groove {
+            swing: 0.0,
+            offsets: vec![0.0, 0.1, -0.2, 0.3],
+        };
+        assert_eq!(groove.row_offset(1), 0.1);
+        assert_eq!(groove.row_offset(6), -0.2);
+        assert_eq!(groove.row_offset(7), 0.3);
+    }
+
+    #[test]
+    fn row_offset_swings_odd_rows_and_clamps() {
+        let groove = Groove {
+            swing: 0.25,
+            offsets: vec![0.1, 0.4],
+        };
+        assert_eq!(groove.row_offset(0), 0.1);
+        assert_eq!(groove.row_offset(3), MAX_GROOVE_OFFSET);
+
+        let groove = Groove {
+            swing: 0.2,
+            offsets: Vec::new(),
+        };
+        assert_eq!(groove.row_offset(0), 0.0);
+        assert_eq!(groove.row_offset(5), 0.2);
+    }
+
+    #[test]
+    fn set_cycle_length_repeats_or_drops_rows() {
+        let mut groove = Groove {
+            swing: 0.0,
+            offsets: vec![0.1, -0.1],
+        };
+        groove.set_cycle_length(4);
+        assert_eq!(groove.offsets, [0.1, -0.1, 0.1, -0.1]);
+        groove.set_cycle_length(2);
+        assert_eq!(groove.offsets, [0.1, -0.1]);
+    }
+}
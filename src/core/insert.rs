@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+// Longest insert chain of a channel.
+pub const MAX_INSERTS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FilterMode {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DistortionMode {
+    SoftClip,
+    HardClip,
+    Foldback,
+}
+
+// An effect in a channel's insert chain, processing the channel after its voices are mixed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InsertConfig {
+    Filter {
+        mode: FilterMode,
+        cutoff: f32,
+        q: f32,
+    },
+    Distortion {
+        mode: DistortionMode,
+        drive: f32,
+        mix: f32,
+    },
+    Chorus {
+        // Modulation depth in milliseconds.
+        depth: f32,
+        feedback: f32,
+        mix: f32,
+    },
+    Compressor {
+        threshold: f32,
+        ratio: f32,
+        attack: f32,
+        release: f32,
+        makeup: f32,
+    },
+}
+
+impl InsertConfig {
+    pub fn default_filter() -> Self {
+        Self::Filter {
+            mode: FilterMode::LowPass,
+            cutoff: 2000.0,
+            q: 0.7,
+        }
+    }
+
+    pub fn default_distortion() -> Self {
+        Self::Distortion {
+            mode: DistortionMode::SoftClip,
+            drive: 4.0,
+            mix: 1.0,
+        }
+    }
+
+    pub fn default_chorus() -> Self {
+        Self::Chorus {
+            depth: 2.0,
+            feedback: 0.2,
+            mix: 0.5,
+        }
+    }
+
+    pub fn default_compressor() -> Self {
+        Self::Compressor {
+            threshold: -18.0,
+            ratio: 4.0,
+            attack: 10.0,
+            release: 100.0,
+            makeup: 6.0,
+        }
+    }
+
+    // Number of editable values, including the mode.
+    pub fn num_params(&self) -> usize {
+        match self {
+            Self::Filter { .. } => 3,
+            Self::Distortion { .. } => 3,
+            Self::Chorus { .. } => 3,
+            Self::Compressor { .. } => 5,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Filter { .. } => "Filter",
+            Self::Distortion { .. } => "Distortion",
+            Self::Chorus { .. } => "Chorus",
+            Self::Compressor { .. } => "Compressor",
+        }
+    }
+}
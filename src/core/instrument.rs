@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+// Copies of an oscillator playing at once, detuned across its unison spread.
+pub const MAX_UNISON: usize = 8;
+// Longest tempo-synced LFO cycle in rows.
+pub const MAX_LFO_SYNC_ROWS: usize = 64;
+
 #[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum WaveformType {
     Sine,
@@ -9,6 +14,67 @@ pub enum WaveformType {
     Noise,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LfoWaveform {
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    // A new random level every cycle (sample and hold)
+    Random,
+}
+
+impl LfoWaveform {
+    pub fn next(self) -> Self {
+        match self {
+            LfoWaveform::Sine => LfoWaveform::Triangle,
+            LfoWaveform::Triangle => LfoWaveform::Saw,
+            LfoWaveform::Saw => LfoWaveform::Square,
+            LfoWaveform::Square => LfoWaveform::Random,
+            LfoWaveform::Random => LfoWaveform::Sine,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            LfoWaveform::Sine => LfoWaveform::Random,
+            LfoWaveform::Triangle => LfoWaveform::Sine,
+            LfoWaveform::Saw => LfoWaveform::Triangle,
+            LfoWaveform::Square => LfoWaveform::Saw,
+            LfoWaveform::Random => LfoWaveform::Square,
+        }
+    }
+}
+
+// The parameter an LFO moves, in every module of the instrument that has it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LfoTarget {
+    // Oscillator frequency, full depth is one octave up and down
+    Pitch,
+    // Filter cutoff, full depth is four octaves up and down
+    Cutoff,
+    // Gain module level, full depth dips to silence
+    Gain,
+}
+
+impl LfoTarget {
+    pub fn next(self) -> Self {
+        match self {
+            LfoTarget::Pitch => LfoTarget::Cutoff,
+            LfoTarget::Cutoff => LfoTarget::Gain,
+            LfoTarget::Gain => LfoTarget::Pitch,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        match self {
+            LfoTarget::Pitch => LfoTarget::Gain,
+            LfoTarget::Cutoff => LfoTarget::Pitch,
+            LfoTarget::Gain => LfoTarget::Cutoff,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ModuleConfig {
     Oscillator {
@@ -17,10 +83,22 @@ pub enum ModuleConfig {
         detune: f32,
         pitch_env_amount: f32,
         pitch_env_decay: f32,
+        // Oscillators add their output at this level to the sound of the modules before them,
+        // so several of them layer
+        #[serde(default = "default_level")]
+        level: f32,
+        #[serde(default = "default_unison")]
+        unison: usize,
+        // Cents between the lowest and the highest unison copy
+        #[serde(default)]
+        unison_spread: f32,
     },
     Filter {
         cutoff: f32,
         resonance: f32,
+        // How much note velocity closes the filter (0 = none, 1 = fully).
+        #[serde(default)]
+        velocity_amount: f32,
     },
     Adsr {
         attack: f32,
@@ -31,6 +109,63 @@ pub enum ModuleConfig {
     Gain {
         level: f32,
     },
+    Lfo {
+        waveform: LfoWaveform,
+        target: LfoTarget,
+        // Cycles per second while not synced
+        rate: f32,
+        // Rows per cycle at the song tempo, 0 runs free at `rate`
+        sync_rows: usize,
+        // 0.0..=1.0 of the target's full depth
+        depth: f32,
+        // Start the cycle over on every note instead of running freely
+        retrigger: bool,
+    },
+}
+
+fn default_level() -> f32 {
+    1.0
+}
+
+fn default_unison() -> usize {
+    1
+}
+
+impl ModuleConfig {
+    pub fn default_oscillator() -> Self {
+        Self::Oscillator {
+            waveform: WaveformType::Sine,
+            pitch_offset: 0.0,
+            detune: 0.0,
+            pitch_env_amount: 0.0,
+            pitch_env_decay: 0.1,
+            level: 1.0,
+            unison: 1,
+            unison_spread: 0.0,
+        }
+    }
+
+    pub fn default_lfo() -> Self {
+        Self::Lfo {
+            waveform: LfoWaveform::Sine,
+            target: LfoTarget::Pitch,
+            rate: 5.0,
+            sync_rows: 0,
+            depth: 0.0,
+            retrigger: true,
+        }
+    }
+
+    // Number of editable values in the instrument view.
+    pub fn num_params(&self) -> usize {
+        match self {
+            Self::Oscillator { .. } => 8,
+            Self::Filter { .. } => 3,
+            Self::Adsr { .. } => 4,
+            Self::Gain { .. } => 1,
+            Self::Lfo { .. } => 6,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -44,13 +179,7 @@ impl Default for Instrument {
         Self {
             name: "Init".to_string(),
             modules: vec![
-                ModuleConfig::Oscillator {
-                    waveform: WaveformType::Sine,
-                    pitch_offset: 0.0,
-                    detune: 0.0,
-                    pitch_env_amount: 0.0,
-                    pitch_env_decay: 0.1,
-                },
+                ModuleConfig::default_oscillator(),
                 ModuleConfig::Adsr {
                     attack: 0.01,
                     decay: 0.1,
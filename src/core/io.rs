@@ -1,41 +1,232 @@
+use super::channel::{ChannelSettings, VoiceMode, default_volume};
+use super::groove::Groove;
 use super::instrument::Instrument;
-use super::pattern::Pattern;
+use super::pattern::{NOTE_OFF, NUM_CHANNELS, Pattern};
+use super::sends::SendSettings;
+use super::state::{DEFAULT_TICKS_PER_ROW, SharedState};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufReader, BufWriter};
 
+// 0: Empty cells released the playing note (files written before versioning).
+// 1: Explicit note-off / note-cut cells.
+// 2: Per-channel voice mode; before, channels 0-2 always retriggered and the rest played legato.
+pub const PROJECT_VERSION: u32 = 2;
+
 #[derive(Serialize, Deserialize)]
 pub struct Project {
+    #[serde(default)]
+    pub version: u32,
     pub bpm: f32,
+    #[serde(default = "default_ticks_per_row")]
+    pub ticks_per_row: usize,
+    #[serde(default = "default_channels")]
+    pub channels: usize,
+    #[serde(default)]
+    pub channel_settings: Vec<ChannelSettings>,
+    #[serde(default = "default_volume")]
+    pub master_volume: f32,
+    #[serde(default)]
+    pub sends: SendSettings,
+    #[serde(default)]
+    pub groove: Groove,
     #[serde(default)]
     pub pattern: Option<Pattern>, // Legacy support
     #[serde(default)]
     pub patterns: Vec<Pattern>,
+    // Song order as pattern indices; empty in old projects, which play patterns in storage order.
+    #[serde(default)]
+    pub order: Vec<usize>,
     pub instruments: Vec<Instrument>,
 }
 
-pub fn save_project(
-    path: &str,
-    bpm: f32,
-    patterns: &[Pattern],
-    instruments: &[Instrument],
-) -> Result<(), Box<dyn std::error::Error>> {
-    let project = Project {
-        bpm,
-        pattern: None,
-        patterns: patterns.to_vec(),
-        instruments: instruments.to_vec(),
-    };
+fn default_ticks_per_row() -> usize {
+    DEFAULT_TICKS_PER_ROW
+}
+
+fn default_channels() -> usize {
+    NUM_CHANNELS
+}
+
+impl Project {
+    pub fn from_state(state: &SharedState) -> Self {
+        Self {
+            version: PROJECT_VERSION,
+            bpm: state.bpm,
+            ticks_per_row: state.ticks_per_row,
+            channels: state.num_channels,
+            channel_settings: state.channel_settings.clone(),
+            master_volume: state.master_volume,
+            sends: state.sends.clone(),
+            groove: state.groove.clone(),
+            pattern: None,
+            patterns: state.patterns.clone(),
+            order: state.order.clone(),
+            instruments: state.instruments.to_vec(),
+        }
+    }
+}
 
+pub fn save_project(path: &str, project: &Project) -> Result<(), Box<dyn std::error::Error>> {
     let file = File::create(path)?;
     let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &project)?;
+    serde_json::to_writer_pretty(writer, project)?;
     Ok(())
 }
 
 pub fn load_project(path: &str) -> Result<Project, Box<dyn std::error::Error>> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
-    let project: Project = serde_json::from_reader(reader)?;
+    let mut project: Project = serde_json::from_reader(reader)?;
+
+    if project.version < 1 {
+        if let Some(pattern) = &mut project.pattern {
+            insert_legacy_note_offs(std::slice::from_mut(pattern));
+        }
+        insert_legacy_note_offs(&mut project.patterns);
+    }
+    if project.version < 2 {
+        if project.channel_settings.len() < 3 {
+            project
+                .channel_settings
+                .resize(3, ChannelSettings::default());
+        }
+        for settings in &mut project.channel_settings[..3] {
+            settings.voice_mode = VoiceMode::Retrigger;
+        }
+    }
+    project.version = PROJECT_VERSION;
+
     Ok(project)
 }
+
+// Old projects relied on an empty cell releasing the note, so turn those cells into note-offs.
+fn insert_legacy_note_offs(patterns: &mut [Pattern]) {
+    let num_channels = patterns
+        .iter()
+        .map(Pattern::num_channels)
+        .max()
+        .unwrap_or(0);
+    for ch in 0..num_channels {
+        // Song playback wraps around, so run once to find whether the last pattern
+        // leaves a note hanging into the first one, then convert on the second pass.
+        let mut sounding = false;
+        for pass in 0..2 {
+            for pattern in patterns.iter_mut() {
+                for row in pattern.rows.iter_mut() {
+                    let Some(note) = row.get_mut(ch) else {
+                        continue;
+                    };
+                    if note.key > 0 {
+                        sounding = true;
+                    } else if sounding && !note.has_effect() {
+                        if pass == 1 {
+                            note.key = NOTE_OFF;
+                        }
+                        sounding = false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::pattern::Note;
+
+    fn note(key: u8) -> Note {
+        Note {
+            key,
+            ..Note::default()
+        }
+    }
+
+    // A temp file of its own for each test and test run.
+    fn temp_path(test: &str, file: &str) -> String {
+        let name = format!("infinitrak_{}_{}_{}.json", std::process::id(), test, file);
+        std::env::temp_dir()
+            .join(name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    // Saves and loads the project again, removing the file before checking the results.
+    fn round_trip(path: &str, project: &Project) -> Project {
+        let saved = save_project(path, project);
+        let loaded = load_project(path);
+        let _ = std::fs::remove_file(path);
+        saved.unwrap();
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn legacy_note_offs_end_notes_at_the_next_empty_cell() {
+        let mut pattern = Pattern::new(4, 2);
+        pattern.rows[0][0] = note(60);
+        pattern.rows[2][1] = note(64);
+        let mut patterns = [pattern];
+
+        insert_legacy_note_offs(&mut patterns);
+
+        let rows = &patterns[0].rows;
+        assert_eq!(rows[0][0].key, 60);
+        assert_eq!(rows[1][0].key, NOTE_OFF);
+        assert_eq!(rows[2][0].key, 0);
+        assert_eq!(rows[3][1].key, NOTE_OFF);
+    }
+
+    #[test]
+    fn legacy_note_offs_keep_effect_cells_and_wrap_around_the_song() {
+        let mut first = Pattern::new(2, 1);
+        first.rows[0][0].effect = 0x1;
+        first.rows[0][0].effect_param = 0x10;
+        let mut last = Pattern::new(2, 1);
+        last.rows[1][0] = note(60);
+        let mut patterns = [first, last];
+
+        insert_legacy_note_offs(&mut patterns);
+
+        // The note hanging over from the last pattern slides on, then ends a row later
+        assert_eq!(patterns[0].rows[0][0].key, 0);
+        assert_eq!(patterns[0].rows[1][0].key, NOTE_OFF);
+        assert_eq!(patterns[1].rows[0][0].key, 0);
+    }
+
+    #[test]
+    fn legacy_projects_are_migrated_on_load() {
+        let mut state = SharedState::new(120.0, 44100.0);
+        state.patterns[0].rows[0][0] = note(60);
+        let mut project = Project::from_state(&state);
+        project.version = 0;
+
+        let test = "legacy_projects_are_migrated_on_load";
+        let loaded = round_trip(&temp_path(test, "legacy"), &project);
+
+        assert_eq!(loaded.version, PROJECT_VERSION);
+        assert_eq!(loaded.patterns[0].rows[0][0].key, 60);
+        assert_eq!(loaded.patterns[0].rows[1][0].key, NOTE_OFF);
+        assert!(
+            loaded.channel_settings[..3]
+                .iter()
+                .all(|settings| settings.voice_mode == VoiceMode::Retrigger)
+        );
+
+        // Saving the migrated project and loading it again changes nothing
+        let reloaded = round_trip(&temp_path(test, "migrated"), &loaded);
+
+        let keys = |project: &Project| {
+            project.patterns[0]
+                .rows
+                .iter()
+                .flatten()
+                .map(|note| note.key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&reloaded), keys(&loaded));
+        assert_eq!(reloaded.bpm, loaded.bpm);
+        assert_eq!(reloaded.ticks_per_row, loaded.ticks_per_row);
+    }
+}
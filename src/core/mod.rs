@@ -1,10 +1,18 @@
+pub mod channel;
+pub mod effect;
+pub mod groove;
+pub mod insert;
 pub mod instrument;
 pub mod io;
 pub mod pattern;
+pub mod sends;
 pub mod state;
 
+pub use channel::VoiceMode;
+pub use effect::Effect;
+pub use insert::InsertConfig;
 pub use instrument::{Instrument, ModuleConfig, WaveformType};
-pub use pattern::{NUM_CHANNELS, ROWS_PER_PATTERN};
+pub use pattern::{MAX_CHANNELS, MAX_VOLUME, MIN_CHANNELS, NOTE_CUT, NOTE_OFF};
 pub use state::SharedState;
 
 pub const NUM_INSTRUMENTS: usize = 32;
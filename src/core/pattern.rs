@@ -1,26 +1,177 @@
+use super::effect::Effect;
+use super::groove::Groove;
 use serde::{Deserialize, Serialize};
 
+// Channel count of new projects; each project may use MIN_CHANNELS..=MAX_CHANNELS.
 pub const NUM_CHANNELS: usize = 16;
+pub const MIN_CHANNELS: usize = 4;
+pub const MAX_CHANNELS: usize = 64;
+// Default length of a new pattern; each pattern may be resized within 1..=MAX_PATTERN_ROWS.
 pub const ROWS_PER_PATTERN: usize = 64;
+pub const MAX_PATTERN_ROWS: usize = 256;
+pub const MAX_VOLUME: u8 = 0x40;
+// Special keys: 0 is an empty cell, which keeps the channel playing.
+pub const NOTE_OFF: u8 = 0xFE;
+pub const NOTE_CUT: u8 = 0xFF;
 
 #[derive(Clone, Copy, Default, Debug, Serialize, Deserialize)]
 pub struct Note {
     pub key: u8,
+    // None: legacy cell, plays the instrument matching the channel index.
+    #[serde(default)]
+    pub instrument: Option<u8>,
+    // None: play at full volume.
+    #[serde(default)]
+    pub volume: Option<u8>,
+    #[serde(default)]
+    pub effect: u8,
+    #[serde(default)]
+    pub effect_param: u8,
+}
+
+impl Note {
+    pub fn is_note(&self) -> bool {
+        self.key > 0 && self.key < 128
+    }
+
+    pub fn instrument_idx(&self, channel: usize) -> usize {
+        match self.instrument {
+            Some(inst) => inst as usize,
+            None => channel,
+        }
+    }
+
+    pub fn velocity(&self) -> f32 {
+        match self.volume {
+            Some(vol) => vol.min(MAX_VOLUME) as f32 / MAX_VOLUME as f32,
+            None => 1.0,
+        }
+    }
+
+    pub fn effect(&self) -> Option<Effect> {
+        Effect::decode(self.effect, self.effect_param)
+    }
+
+    pub fn has_effect(&self) -> bool {
+        self.effect != 0 || self.effect_param != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.key == 0 && self.instrument.is_none() && self.volume.is_none() && !self.has_effect()
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Pattern {
-    // Use Vec for rows to avoid serde array limit issues.
-    // Inner array [Note; 16] is supported by serde (<= 32).
-    pub rows: Vec<[Note; NUM_CHANNELS]>,
+    // One Vec<Note> per row, as wide as the project's channel count.
+    pub rows: Vec<Vec<Note>>,
+    // Replaces the project's groove while this pattern plays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub groove: Option<Groove>,
 }
 
-impl Default for Pattern {
-    fn default() -> Self {
-        let mut rows = Vec::with_capacity(ROWS_PER_PATTERN);
-        for _ in 0..ROWS_PER_PATTERN {
-            rows.push([Note::default(); NUM_CHANNELS]);
+impl Pattern {
+    pub fn new(len: usize, num_channels: usize) -> Self {
+        let mut rows = Vec::with_capacity(len);
+        for _ in 0..len {
+            rows.push(vec![Note::default(); num_channels]);
+        }
+        Self { rows, groove: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn num_channels(&self) -> usize {
+        self.rows.first().map_or(0, |row| row.len())
+    }
+
+    pub fn is_channel_empty(&self, channel: usize) -> bool {
+        self.rows
+            .iter()
+            .all(|row| row.get(channel).is_none_or(Note::is_empty))
+    }
+
+    // Adds empty channels or drops channels on the right.
+    pub fn set_num_channels(&mut self, num_channels: usize) {
+        for row in &mut self.rows {
+            row.resize(num_channels, Note::default());
+        }
+    }
+
+    // Grows with empty rows or truncates at the end.
+    pub fn resize(&mut self, len: usize) {
+        let len = len.clamp(1, MAX_PATTERN_ROWS);
+        let num_channels = self.num_channels();
+        self.rows.resize(len, vec![Note::default(); num_channels]);
+    }
+
+    // Doubles the length by repeating the pattern.
+    pub fn double(&mut self) {
+        let len = self.len();
+        if len * 2 <= MAX_PATTERN_ROWS {
+            self.rows.extend_from_within(..);
         }
-        Self { rows }
+    }
+
+    pub fn halve(&mut self) {
+        self.resize(self.len() / 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_pads_with_empty_rows_and_clamps_the_length() {
+        let mut pattern = Pattern::new(4, 3);
+        pattern.rows[0][0].key = 60;
+
+        pattern.resize(6);
+        assert_eq!(pattern.len(), 6);
+        assert_eq!(pattern.rows[5].len(), 3);
+        assert!(pattern.rows[4..].iter().flatten().all(Note::is_empty));
+
+        pattern.resize(0);
+        assert_eq!(pattern.len(), 1);
+        assert_eq!(pattern.rows[0][0].key, 60);
+
+        pattern.resize(MAX_PATTERN_ROWS + 1);
+        assert_eq!(pattern.len(), MAX_PATTERN_ROWS);
+    }
+
+    #[test]
+    fn double_repeats_the_pattern_up_to_the_maximum() {
+        let mut pattern = Pattern::new(3, 1);
+        pattern.rows[1][0].key = 60;
+
+        pattern.double();
+        assert_eq!(pattern.len(), 6);
+        assert_eq!(pattern.rows[4][0].key, 60);
+
+        let mut full = Pattern::new(MAX_PATTERN_ROWS / 2 + 1, 1);
+        full.double();
+        assert_eq!(full.len(), MAX_PATTERN_ROWS / 2 + 1);
+    }
+
+    #[test]
+    fn halve_keeps_the_first_half() {
+        let mut pattern = Pattern::new(8, 1);
+        pattern.rows[3][0].key = 60;
+        pattern.rows[4][0].key = 62;
+
+        pattern.halve();
+        assert_eq!(pattern.len(), 4);
+        assert_eq!(pattern.rows[3][0].key, 60);
+
+        let mut single = Pattern::new(1, 1);
+        single.halve();
+        assert_eq!(single.len(), 1);
     }
 }
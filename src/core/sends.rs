@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+// Longest delay time, also the size of the delay lines.
+pub const MAX_DELAY_SECONDS: f32 = 4.0;
+pub const MAX_DELAY_ROWS: f32 = 32.0;
+
+// Global send effects; channels feed them through their send amounts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SendSettings {
+    pub reverb_room_size: f32,
+    pub reverb_damping: f32,
+    pub reverb_return: f32,
+    // Delay times in rows, so they follow the tempo
+    pub delay_rows_left: f32,
+    pub delay_rows_right: f32,
+    pub delay_feedback: f32,
+    pub delay_return: f32,
+}
+
+impl Default for SendSettings {
+    fn default() -> Self {
+        Self {
+            reverb_room_size: 0.8,
+            reverb_damping: 0.2,
+            reverb_return: 1.0,
+            delay_rows_left: 3.0,
+            delay_rows_right: 4.0,
+            delay_feedback: 0.4,
+            delay_return: 1.0,
+        }
+    }
+}
+
+impl SendSettings {
+    // Seconds per delay row at `bpm` (4 rows per beat), limited to the delay line size.
+    pub fn delay_seconds(rows: f32, bpm: f32) -> f32 {
+        (rows * 60.0 / (bpm * 4.0)).clamp(0.0, MAX_DELAY_SECONDS)
+    }
+}
@@ -1,6 +1,14 @@
 use super::NUM_INSTRUMENTS;
+use super::channel::{ChannelSettings, VoiceMode};
+use super::groove::Groove;
 use super::instrument::{Instrument, ModuleConfig, WaveformType};
-use super::pattern::Pattern;
+use super::pattern::{MAX_CHANNELS, MIN_CHANNELS, NUM_CHANNELS, Pattern, ROWS_PER_PATTERN};
+use super::sends::SendSettings;
+
+pub const DEFAULT_TICKS_PER_ROW: usize = 6;
+// Tempo range for BPM changes from the keyboard and direct entry.
+pub const MIN_BPM: f32 = 10.0;
+pub const MAX_BPM: f32 = 300.0;
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum PlayMode {
@@ -8,23 +16,80 @@ pub enum PlayMode {
     Song,
 }
 
+// When a pattern picked during playback takes over from the playing one.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub enum PatternLaunch {
+    // Right away, at the playing row
+    #[default]
+    Immediate,
+    // Once the playing pattern has ended
+    PatternEnd,
+    // At the next row that is a multiple of this many rows
+    Rows(usize),
+}
+
+impl PatternLaunch {
+    pub fn next(self) -> Self {
+        match self {
+            PatternLaunch::Immediate => PatternLaunch::PatternEnd,
+            PatternLaunch::PatternEnd => PatternLaunch::Rows(4),
+            PatternLaunch::Rows(rows) if rows < 16 => PatternLaunch::Rows(rows * 2),
+            PatternLaunch::Rows(_) => PatternLaunch::Immediate,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            PatternLaunch::Immediate => "Immediate".to_string(),
+            PatternLaunch::PatternEnd => "Pattern End".to_string(),
+            PatternLaunch::Rows(rows) => format!("Every {} Rows", rows),
+        }
+    }
+}
+
+// Song and transport state. The UI edits its own copy and sends copies to the audio engine,
+// which owns the one that plays and reports its position back (see `audio::control`).
 #[derive(Clone)]
 pub struct SharedState {
+    pub num_channels: usize,
+    pub channel_settings: Vec<ChannelSettings>,
+    pub master_volume: f32,
+    pub sends: SendSettings,
+    // Row timing of patterns without a groove of their own
+    pub groove: Groove,
+    // Level meters, peak per channel and of the master bus, published by the audio engine
+    pub channel_peaks: Vec<f32>,
+    pub master_peak: f32,
     pub patterns: Vec<Pattern>,
     pub current_pattern: usize,
+    // Song order: pattern index per song position
+    pub order: Vec<usize>,
+    pub current_order: usize,
     pub instruments: [Instrument; NUM_INSTRUMENTS],
     pub current_row: usize,
     pub is_playing: bool,
+    // Rows looped in whichever pattern plays, first and last included
+    pub loop_rows: Option<(usize, usize)>,
+    // Song positions looped in song mode, first and last included
+    pub loop_orders: Option<(usize, usize)>,
+    // Playback stops once this row has played, to play a single row or a block
+    pub stop_after_row: Option<usize>,
+    pub pattern_launch: PatternLaunch,
+    // Pattern waiting to take over, set by the engine when the launch is not immediate
+    pub queued_pattern: Option<usize>,
     pub play_mode: PlayMode,
     pub bpm: f32,
-    pub samples_per_tick: usize,
-    pub current_tick_samples: usize,
-    pub preview_request: Option<(usize, u8)>,
+    pub ticks_per_row: usize,
+    pub sample_rate: f32,
+    // Tick length and the samples played since the last tick, both fractional so the
+    // remainder carries over and long songs keep time at any BPM
+    pub samples_per_tick: f64,
+    pub current_tick_samples: f64,
 }
 
 impl SharedState {
     pub fn new(bpm: f32, sample_rate: f32) -> Self {
-        let samples_per_row = (sample_rate * 60.0) / (bpm * 4.0);
+        let samples_per_tick = Self::tick_length(sample_rate, bpm, DEFAULT_TICKS_PER_ROW);
 
         let mut instruments = Vec::with_capacity(NUM_INSTRUMENTS);
         for _ in 0..NUM_INSTRUMENTS {
@@ -40,10 +105,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 150.0,
                 pitch_env_decay: 0.05,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 2000.0,
                 resonance: 0.0,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.001,
@@ -63,10 +132,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 10000.0,
                 resonance: 0.0,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.001,
@@ -86,10 +159,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 3000.0,
                 resonance: 0.2,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.001,
@@ -109,10 +186,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 400.0,
                 resonance: 0.4,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.01,
@@ -132,10 +213,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 3000.0,
                 resonance: 0.2,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.02,
@@ -155,10 +240,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 2000.0,
                 resonance: 0.0,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.001,
@@ -178,10 +267,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 800.0,
                 resonance: 0.1,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.5,
@@ -201,10 +294,14 @@ impl SharedState {
                 detune: 0.0,
                 pitch_env_amount: 0.0,
                 pitch_env_decay: 0.1,
+                level: 1.0,
+                unison: 1,
+                unison_spread: 0.0,
             },
             ModuleConfig::Filter {
                 cutoff: 600.0,
                 resonance: 0.8,
+                velocity_amount: 0.0,
             },
             ModuleConfig::Adsr {
                 attack: 0.01,
@@ -218,32 +315,226 @@ impl SharedState {
         let instruments_array: [Instrument; NUM_INSTRUMENTS] =
             instruments.try_into().expect("Wrong size");
 
+        // Drum channels restart their sound on every hit
+        let mut channel_settings = vec![ChannelSettings::default(); NUM_CHANNELS];
+        for settings in &mut channel_settings[..3] {
+            settings.voice_mode = VoiceMode::Retrigger;
+        }
+
         Self {
-            patterns: vec![Pattern::default()],
+            num_channels: NUM_CHANNELS,
+            channel_settings,
+            master_volume: 1.0,
+            sends: SendSettings::default(),
+            groove: Groove::default(),
+            channel_peaks: vec![0.0; NUM_CHANNELS],
+            master_peak: 0.0,
+            patterns: vec![Pattern::new(ROWS_PER_PATTERN, NUM_CHANNELS)],
             current_pattern: 0,
+            order: vec![0],
+            current_order: 0,
             instruments: instruments_array,
             current_row: 0,
             is_playing: false,
+            loop_rows: None,
+            loop_orders: None,
+            stop_after_row: None,
+            pattern_launch: PatternLaunch::default(),
+            queued_pattern: None,
             play_mode: PlayMode::Pattern,
             bpm,
-            samples_per_tick: samples_per_row as usize,
-            current_tick_samples: samples_per_row as usize,
-            preview_request: None,
+            ticks_per_row: DEFAULT_TICKS_PER_ROW,
+            sample_rate,
+            samples_per_tick,
+            current_tick_samples: samples_per_tick,
+        }
+    }
+
+    // Row length follows BPM (4 rows per beat); ticks only subdivide a row.
+    fn tick_length(sample_rate: f32, bpm: f32, ticks_per_row: usize) -> f64 {
+        (sample_rate as f64 * 60.0) / (bpm as f64 * 4.0 * ticks_per_row as f64)
+    }
+
+    pub fn set_timing(&mut self, bpm: f32, ticks_per_row: usize) {
+        self.bpm = bpm;
+        self.ticks_per_row = ticks_per_row.max(1);
+        self.samples_per_tick = Self::tick_length(self.sample_rate, self.bpm, self.ticks_per_row);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_timing(self.bpm, self.ticks_per_row);
+    }
+
+    // Song position, pattern and row where playback from `row` starts; song mode starts at
+    // the song position of the edited pattern.
+    pub fn play_position(&self, row: usize) -> (usize, usize, usize) {
+        let mut order = self.current_order;
+        let mut pattern = self.current_pattern;
+        if self.play_mode == PlayMode::Song && self.order.get(order) != Some(&pattern) {
+            match self.order.iter().position(|&idx| idx == pattern) {
+                Some(pos) => order = pos,
+                None => {
+                    order = order.min(self.order.len() - 1);
+                    pattern = self.order[order];
+                }
+            }
+        }
+        (order, pattern, row.min(self.patterns[pattern].len() - 1))
+    }
+
+    // Starts playback at a position from `play_position`.
+    pub fn play_at(&mut self, order: usize, pattern: usize, row: usize) {
+        self.current_order = order.min(self.order.len() - 1);
+        self.current_pattern = pattern.min(self.patterns.len() - 1);
+        self.current_row = row.min(self.patterns[self.current_pattern].len() - 1);
+        self.current_tick_samples = self.samples_per_tick;
+        self.queued_pattern = None;
+        self.is_playing = true;
+    }
+
+    pub fn groove(&self, pattern: usize) -> &Groove {
+        self.patterns[pattern]
+            .groove
+            .as_ref()
+            .unwrap_or(&self.groove)
+    }
+
+    pub fn groove_mut(&mut self, pattern: usize) -> &mut Groove {
+        match &mut self.patterns[pattern].groove {
+            Some(groove) => groove,
+            None => &mut self.groove,
+        }
+    }
+
+    pub fn new_pattern(&self) -> Pattern {
+        Pattern::new(ROWS_PER_PATTERN, self.num_channels)
+    }
+
+    // Settings of removed channels are kept, so adding the channel back restores its mixer
+    // strip; only channels without notes should be removed (see `is_channel_empty`).
+    pub fn set_num_channels(&mut self, num_channels: usize) {
+        self.num_channels = num_channels.clamp(MIN_CHANNELS, MAX_CHANNELS);
+        if self.channel_settings.len() < self.num_channels {
+            self.channel_settings
+                .resize(self.num_channels, ChannelSettings::default());
+        }
+        for pattern in &mut self.patterns {
+            pattern.set_num_channels(self.num_channels);
+        }
+    }
+
+    // True when no pattern has anything in the channel.
+    pub fn is_channel_empty(&self, channel: usize) -> bool {
+        self.patterns
+            .iter()
+            .all(|pattern| pattern.is_channel_empty(channel))
+    }
+
+    pub fn toggle_mute(&mut self, channel: usize) {
+        if let Some(settings) = self.channel_settings.get_mut(channel) {
+            settings.muted = !settings.muted;
+        }
+    }
+
+    pub fn toggle_solo(&mut self, channel: usize) {
+        if let Some(settings) = self.channel_settings.get_mut(channel) {
+            settings.solo = !settings.solo;
+        }
+    }
+
+    pub fn cycle_voice_mode(&mut self, channel: usize) {
+        if let Some(settings) = self.channel_settings.get_mut(channel) {
+            settings.voice_mode = settings.voice_mode.next();
+        }
+    }
+
+    pub fn clear_mute_solo(&mut self) {
+        for settings in &mut self.channel_settings {
+            settings.muted = false;
+            settings.solo = false;
+        }
+    }
+
+    // Once any channel is soloed, only soloed channels are heard.
+    pub fn is_channel_audible(&self, channel: usize) -> bool {
+        let any_solo = self.channel_settings[..self.num_channels]
+            .iter()
+            .any(|settings| settings.solo);
+        match self.channel_settings.get(channel) {
+            Some(settings) => !settings.muted && (settings.solo || !any_solo),
+            None => true,
+        }
+    }
+
+    pub fn insert_pattern(&mut self, idx: usize, pattern: Pattern) {
+        self.patterns.insert(idx, pattern);
+        for entry in &mut self.order {
+            if *entry >= idx {
+                *entry += 1;
+            }
+        }
+    }
+
+    // Removes the pattern and every song position that played it.
+    pub fn remove_pattern(&mut self, idx: usize) {
+        if self.patterns.len() <= 1 {
+            return;
+        }
+        self.patterns.remove(idx);
+        self.order.retain(|&entry| entry != idx);
+        for entry in &mut self.order {
+            if *entry > idx {
+                *entry -= 1;
+            }
+        }
+        if self.order.is_empty() {
+            self.order.push(0);
+        }
+        self.current_order = self.current_order.min(self.order.len() - 1);
+        if self.current_pattern >= self.patterns.len() {
+            self.current_pattern = self.patterns.len() - 1;
         }
     }
 
     pub fn load_project(&mut self, project: super::io::Project) {
-        self.bpm = project.bpm;
+        self.set_timing(project.bpm.clamp(MIN_BPM, MAX_BPM), project.ticks_per_row);
 
         if !project.patterns.is_empty() {
             self.patterns = project.patterns;
         } else if let Some(p) = project.pattern {
             self.patterns = vec![p];
         } else {
-            self.patterns = vec![Pattern::default()];
+            self.patterns = vec![];
+        }
+
+        for pattern in &mut self.patterns {
+            if pattern.is_empty() {
+                *pattern = Pattern::new(ROWS_PER_PATTERN, project.channels);
+            }
+        }
+        if self.patterns.is_empty() {
+            self.patterns
+                .push(Pattern::new(ROWS_PER_PATTERN, project.channels));
+        }
+        // Also pads rows and channel settings that were saved narrower than the project
+        self.channel_settings = project.channel_settings;
+        self.set_num_channels(project.channels);
+        self.master_volume = project.master_volume;
+        self.sends = project.sends;
+        self.groove = project.groove;
+
+        self.order = project
+            .order
+            .into_iter()
+            .filter(|&idx| idx < self.patterns.len())
+            .collect();
+        if self.order.is_empty() {
+            self.order = (0..self.patterns.len()).collect();
         }
 
-        self.current_pattern = 0;
+        self.current_pattern = self.order[0];
+        self.current_order = 0;
 
         let mut instruments_vec = Vec::with_capacity(NUM_INSTRUMENTS);
         for _ in 0..NUM_INSTRUMENTS {
@@ -257,8 +548,5 @@ impl SharedState {
         }
 
         self.instruments = instruments_vec.try_into().expect("Wrong size");
-
-        let sample_rate = (self.samples_per_tick as f32 * self.bpm * 4.0) / 60.0;
-        self.samples_per_tick = ((sample_rate * 60.0) / (self.bpm * 4.0)) as usize;
     }
 }
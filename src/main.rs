@@ -2,14 +2,13 @@ mod audio;
 mod core;
 mod ui;
 
-use crate::audio::TrackerEngine;
+use crate::audio::{MAX_BLOCK_FRAMES, TrackerEngine};
 use crate::core::SharedState;
 use crate::ui::{App, run_app};
 use cpal::SizedSample;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use infinitedsp_core::core::channels::Mono;
+use infinitedsp_core::core::channels::Stereo;
 use infinitedsp_core::core::frame_processor::FrameProcessor;
-use std::sync::{Arc, Mutex};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting InfiniTrak...");
@@ -21,22 +20,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("no output device available");
     let config = device.default_output_config()?;
 
-    // Create shared state
+    // The UI edits its state and keeps the engine's copy up to date through the handle
     let sample_rate = config.sample_rate() as f32;
-    let state = Arc::new(Mutex::new(SharedState::new(120.0, sample_rate)));
+    let state = SharedState::new(120.0, sample_rate);
+    let (engine, handle) = TrackerEngine::new(sample_rate, state.clone());
 
     // Start audio stream
     let stream = match config.sample_format() {
-        cpal::SampleFormat::F32 => run_audio::<f32>(&device, &config.into(), state.clone())?,
-        cpal::SampleFormat::I16 => run_audio::<i16>(&device, &config.into(), state.clone())?,
-        cpal::SampleFormat::U16 => run_audio::<u16>(&device, &config.into(), state.clone())?,
+        cpal::SampleFormat::F32 => run_audio::<f32>(&device, &config.into(), engine)?,
+        cpal::SampleFormat::I16 => run_audio::<i16>(&device, &config.into(), engine)?,
+        cpal::SampleFormat::U16 => run_audio::<u16>(&device, &config.into(), engine)?,
         sample_format => panic!("Unsupported sample format '{sample_format}'"),
     };
 
     stream.play()?;
 
     // Start TUI
-    let app = App::new(state);
+    let app = App::new(state, handle);
     run_app(app)?;
 
     Ok(())
@@ -45,40 +45,38 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 fn run_audio<T>(
     device: &cpal::Device,
     config: &cpal::StreamConfig,
-    state: Arc<Mutex<SharedState>>,
+    mut engine: TrackerEngine,
 ) -> Result<cpal::Stream, Box<dyn std::error::Error>>
 where
     T: cpal::Sample + cpal::FromSample<f32> + SizedSample,
 {
-    let sample_rate = config.sample_rate as f32;
     let channels = config.channels as usize;
 
-    let mut engine = TrackerEngine::new(sample_rate, state);
-
     let mut sample_index = 0u64;
-    let mut processing_buffer = Vec::with_capacity(1024);
+    let mut processing_buffer = vec![0.0; MAX_BLOCK_FRAMES * 2];
     let err_fn = |err| eprintln!("an error occurred on stream: {}", err);
 
     let stream = device.build_output_stream(
         config,
         move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
-            let frames = data.len() / channels;
-
-            if processing_buffer.len() < frames {
-                processing_buffer.resize(frames, 0.0);
-            }
-
-            let buffer_slice = &mut processing_buffer[0..frames];
-
-            FrameProcessor::<Mono>::process(&mut engine, buffer_slice, sample_index);
-
-            sample_index += frames as u64;
-
-            for (i, frame) in data.chunks_mut(channels).enumerate() {
-                let sample_val = buffer_slice[i];
-                let sample = cpal::Sample::from_sample(sample_val);
-                for out_sample in frame.iter_mut() {
-                    *out_sample = sample;
+            for block in data.chunks_mut(MAX_BLOCK_FRAMES * channels) {
+                let frames = block.len() / channels;
+                let buffer_slice = &mut processing_buffer[0..frames * 2];
+
+                FrameProcessor::<Stereo>::process(&mut engine, buffer_slice, sample_index);
+
+                sample_index += frames as u64;
+
+                for (frame, stereo) in block.chunks_mut(channels).zip(buffer_slice.chunks(2)) {
+                    if channels == 1 {
+                        frame[0] = cpal::Sample::from_sample((stereo[0] + stereo[1]) * 0.5);
+                        continue;
+                    }
+                    // Left / right go to the first two outputs, any others stay silent
+                    for (i, out_sample) in frame.iter_mut().enumerate() {
+                        let sample_val = if i < 2 { stereo[i] } else { 0.0 };
+                        *out_sample = cpal::Sample::from_sample(sample_val);
+                    }
                 }
             }
         },
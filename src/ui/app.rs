@@ -1,5 +1,9 @@
-use super::input::{handle_file_dialog_input, handle_instrument_input, handle_pattern_input};
+use super::input::{
+    handle_bpm_entry_input, handle_file_dialog_input, handle_groove_input, handle_instrument_input,
+    handle_mixer_input, handle_order_input, handle_pattern_input, handle_strip_input,
+};
 use super::view::ui;
+use crate::audio::{EngineCommand, EngineHandle};
 use crate::core::SharedState;
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
@@ -11,13 +15,27 @@ use ratatui::{
     backend::{Backend, CrosstermBackend},
     widgets::{ListState, TableState},
 };
+use std::cell::RefCell;
 use std::io;
-use std::sync::{Arc, Mutex};
+use std::rc::Rc;
 
 #[derive(PartialEq)]
 pub enum View {
     Pattern,
     Instrument,
+    Order,
+    Mixer,
+    Strip,
+    Groove,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PatternColumn {
+    Note,
+    Instrument,
+    Volume,
+    Effect,
+    EffectParam,
 }
 
 #[derive(PartialEq)]
@@ -27,9 +45,12 @@ pub enum InstrumentFocus {
 }
 
 pub struct App {
-    pub state: Arc<Mutex<SharedState>>,
+    // The UI's copy of the song; the engine gets a copy whenever an input handler reports an edit
+    pub state: Rc<RefCell<SharedState>>,
+    pub engine: EngineHandle,
     pub cursor_row: usize,
     pub cursor_channel: usize,
+    pub cursor_column: PatternColumn,
     pub current_octave: u8,
     pub current_view: View,
     pub current_instrument_idx: usize,
@@ -38,10 +59,24 @@ pub struct App {
     pub param_idx: usize,
 
     pub edit_step: usize,
+    // Row where the block selection started; the block spans from here to the cursor row
+    pub block_anchor: Option<usize>,
+
+    pub order_cursor: usize,
+    pub mixer_cursor: usize,
+    // Selected insert parameter in the channel strip of `cursor_channel`
+    pub strip_param_idx: usize,
+    // Selected groove row, 0 is the swing amount
+    pub groove_cursor: usize,
 
     // UI States for scrolling
+    pub channel_offset: usize,
     pub inst_list_state: ListState,
+    pub order_list_state: ListState,
     pub param_table_state: TableState,
+    pub mixer_table_state: TableState,
+    pub strip_table_state: TableState,
+    pub groove_table_state: TableState,
 
     // Status message
     pub status_message: String,
@@ -56,33 +91,60 @@ pub struct App {
     pub show_help_dialog: bool,
     pub help_list_state: ListState,
 
+    // BPM typed so far while the tempo prompt is open
+    pub bpm_entry: Option<String>,
+
     // Current Project File
     pub current_filename: Option<String>,
 }
 
 impl App {
-    pub fn new(state: Arc<Mutex<SharedState>>) -> App {
+    pub fn new(state: SharedState, engine: EngineHandle) -> App {
         let mut inst_list_state = ListState::default();
         inst_list_state.select(Some(0));
 
         let mut param_table_state = TableState::default();
         param_table_state.select(Some(0));
 
+        let mut order_list_state = ListState::default();
+        order_list_state.select(Some(0));
+
+        let mut mixer_table_state = TableState::default();
+        mixer_table_state.select(Some(0));
+
+        let mut strip_table_state = TableState::default();
+        strip_table_state.select(Some(0));
+
+        let mut groove_table_state = TableState::default();
+        groove_table_state.select(Some(0));
+
         let file_list_state = ListState::default();
         let help_list_state = ListState::default();
 
         App {
-            state,
+            state: Rc::new(RefCell::new(state)),
+            engine,
             cursor_row: 0,
             cursor_channel: 0,
+            cursor_column: PatternColumn::Note,
             current_octave: 4,
             current_view: View::Pattern,
             current_instrument_idx: 0,
             inst_focus: InstrumentFocus::List,
             param_idx: 0,
             edit_step: 1,
+            block_anchor: None,
+            order_cursor: 0,
+            mixer_cursor: 0,
+            strip_param_idx: 0,
+            groove_cursor: 0,
+            channel_offset: 0,
             inst_list_state,
+            order_list_state,
             param_table_state,
+            mixer_table_state,
+            strip_table_state,
+            groove_table_state,
             status_message: String::from("Welcome to InfiniTrak! Press ? for help."),
             status_timer: 100,
             show_file_dialog: false,
@@ -90,10 +152,17 @@ impl App {
             file_list_state,
             show_help_dialog: false,
             help_list_state,
+            bpm_entry: None,
             current_filename: None,
         }
     }
 
+    // First and last row of the block selection.
+    pub fn selected_rows(&self) -> Option<(usize, usize)> {
+        self.block_anchor
+            .map(|anchor| (anchor.min(self.cursor_row), anchor.max(self.cursor_row)))
+    }
+
     pub fn set_status(&mut self, msg: String) {
         self.status_message = msg;
         self.status_timer = 100;
@@ -122,11 +191,37 @@ pub fn run_app(mut app: App) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+// Playback starts and stops in the engine; the UI follows once the engine reports it.
+fn toggle_playback(app: &mut App, row: usize) {
+    let command = {
+        let state = app.state.borrow();
+        if state.is_playing {
+            EngineCommand::Stop
+        } else {
+            let (order, pattern, row) = state.play_position(row);
+            EngineCommand::Play {
+                order,
+                pattern,
+                row,
+                stop_after: None,
+            }
+        }
+    };
+    app.engine.send(command);
+}
+
 fn run_main_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()>
 where
     <B as Backend>::Error: Send + Sync + 'static,
 {
+    let mut edited = false;
     loop {
+        if edited {
+            app.engine.send_song(&app.state.borrow());
+            edited = false;
+        }
+        app.engine.sync(&mut app.state.borrow_mut());
+
         if let Err(e) = terminal.draw(|f| ui(f, app)) {
             return Err(io::Error::other(e));
         }
@@ -170,7 +265,12 @@ where
                 }
 
                 if app.show_file_dialog {
-                    handle_file_dialog_input(key.code, app);
+                    edited = handle_file_dialog_input(key.code, app);
+                    continue;
+                }
+
+                if app.bpm_entry.is_some() {
+                    handle_bpm_entry_input(key.code, app);
                     continue;
                 }
 
@@ -184,43 +284,39 @@ where
                     KeyCode::Tab => {
                         app.current_view = match app.current_view {
                             View::Pattern => View::Instrument,
-                            View::Instrument => View::Pattern,
+                            View::Instrument => View::Order,
+                            View::Order => View::Mixer,
+                            View::Mixer => View::Strip,
+                            View::Strip => View::Groove,
+                            View::Groove => View::Pattern,
                         };
                     }
                     KeyCode::Char(' ') => {
-                        let mut state = app.state.lock().unwrap();
-                        if !state.is_playing {
-                            if key
-                                .modifiers
-                                .contains(crossterm::event::KeyModifiers::SHIFT)
-                            {
-                                state.current_row = app.cursor_row;
-                            } else {
-                                state.current_row = 0;
-                            }
-                            state.current_tick_samples = state.samples_per_tick;
-                            state.is_playing = true;
+                        let row = if key
+                            .modifiers
+                            .contains(crossterm::event::KeyModifiers::SHIFT)
+                        {
+                            app.cursor_row
                         } else {
-                            state.is_playing = false;
-                        }
+                            0
+                        };
+                        toggle_playback(app, row);
                     }
                     KeyCode::Enter if app.current_view == View::Pattern => {
-                        let mut state = app.state.lock().unwrap();
-                        if !state.is_playing {
-                            state.current_row = app.cursor_row;
-                            state.current_tick_samples = state.samples_per_tick;
-                            state.is_playing = true;
-                        } else {
-                            state.is_playing = false;
-                        }
+                        toggle_playback(app, app.cursor_row);
                     }
                     _ => {}
                 }
 
-                match app.current_view {
+                // The engine only gets a new copy of the song when a key changed it
+                edited = match app.current_view {
                     View::Pattern => handle_pattern_input(key, app),
                     View::Instrument => handle_instrument_input(key.code, app),
-                }
+                    View::Order => handle_order_input(key, app),
+                    View::Mixer => handle_mixer_input(key, app),
+                    View::Strip => handle_strip_input(key, app),
+                    View::Groove => handle_groove_input(key, app),
+                };
             }
         }
     }
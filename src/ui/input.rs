@@ -1,14 +1,23 @@
-use super::app::{App, InstrumentFocus};
-use crate::audio::render_to_wav;
-use crate::core::io::{load_project, save_project};
-use crate::core::pattern::Pattern;
-use crate::core::state::PlayMode;
-use crate::core::{ModuleConfig, NUM_CHANNELS, NUM_INSTRUMENTS, ROWS_PER_PATTERN, WaveformType};
+use super::app::{App, InstrumentFocus, PatternColumn, View};
+use crate::audio::{EngineCommand, render_to_wav};
+use crate::core::channel::{MAX_FADER_GAIN, MAX_VOICES, VoiceStealing};
+use crate::core::groove::{GROOVE_LENGTHS, MAX_GROOVE_OFFSET};
+use crate::core::insert::{DistortionMode, FilterMode, MAX_INSERTS};
+use crate::core::instrument::{MAX_LFO_SYNC_ROWS, MAX_UNISON};
+use crate::core::io::{Project, load_project, save_project};
+use crate::core::pattern::Note;
+use crate::core::sends::MAX_DELAY_ROWS;
+use crate::core::state::{MAX_BPM, MIN_BPM, PatternLaunch, PlayMode};
+use crate::core::{
+    InsertConfig, MAX_CHANNELS, MAX_VOLUME, MIN_CHANNELS, ModuleConfig, NOTE_CUT, NOTE_OFF,
+    NUM_INSTRUMENTS, WaveformType,
+};
 use crossterm::event::{self, KeyCode};
 use std::fs;
 use std::path::Path;
 
-pub fn handle_file_dialog_input(code: KeyCode, app: &mut App) {
+pub fn handle_file_dialog_input(code: KeyCode, app: &mut App) -> bool {
+    let mut edited = false;
     match code {
         KeyCode::Esc => {
             app.show_file_dialog = false;
@@ -27,29 +36,112 @@ pub fn handle_file_dialog_input(code: KeyCode, app: &mut App) {
         }
         KeyCode::Enter => {
             if let Some(selected) = app.file_list_state.selected()
-                && selected < app.file_list.len() {
-                    let filename = app.file_list[selected].clone();
-                    match load_project(&filename) {
-                        Ok(project) => {
-                            {
-                                let mut state = app.state.lock().unwrap();
-                                state.load_project(project);
-                            }
-                            app.set_status(format!("Loaded {}", filename));
-                            app.current_filename = Some(filename);
-                            app.show_file_dialog = false;
-                        }
-                        Err(e) => {
-                            app.set_status(format!("Error loading: {}", e));
+                && selected < app.file_list.len()
+            {
+                let filename = app.file_list[selected].clone();
+                match load_project(&filename) {
+                    Ok(project) => {
+                        {
+                            let mut state = app.state.borrow_mut();
+                            state.load_project(project);
+                            app.engine.send(EngineCommand::SetTiming {
+                                bpm: state.bpm,
+                                ticks_per_row: state.ticks_per_row,
+                            });
                         }
+                        app.set_status(format!("Loaded {}", filename));
+                        app.current_filename = Some(filename);
+                        app.show_file_dialog = false;
+                        edited = true;
+                    }
+                    Err(e) => {
+                        app.set_status(format!("Error loading: {}", e));
                     }
                 }
+            }
+        }
+        _ => {}
+    }
+    edited
+}
+
+pub fn handle_bpm_entry_input(code: KeyCode, app: &mut App) {
+    let Some(entry) = &mut app.bpm_entry else {
+        return;
+    };
+
+    match code {
+        KeyCode::Esc => app.bpm_entry = None,
+        KeyCode::Backspace => {
+            entry.pop();
+        }
+        KeyCode::Char(c) if (c.is_ascii_digit() || c == '.') && entry.len() < 7 => {
+            entry.push(c);
+        }
+        KeyCode::Enter => {
+            match entry.parse::<f32>() {
+                Ok(bpm) if (MIN_BPM..=MAX_BPM).contains(&bpm) => {
+                    set_bpm(app, bpm);
+                    app.set_status(format!("BPM: {}", bpm));
+                }
+                _ => app.set_status(format!("BPM must be between {} and {}", MIN_BPM, MAX_BPM)),
+            }
+            app.bpm_entry = None;
         }
         _ => {}
     }
 }
 
-pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
+// Changes the tempo of the UI's copy and the engine's at once, keeping ticks per row.
+fn set_bpm(app: &mut App, bpm: f32) {
+    let mut state = app.state.borrow_mut();
+    let ticks_per_row = state.ticks_per_row;
+    state.set_timing(bpm, ticks_per_row);
+    app.engine
+        .send(EngineCommand::SetTiming { bpm, ticks_per_row });
+}
+
+// Plays rows `first` to `last` of the current pattern once, then stops.
+fn play_rows(app: &mut App, first: usize, last: usize) {
+    let (order, pattern, row) = app.state.borrow().play_position(first);
+    app.engine.send(EngineCommand::Play {
+        order,
+        pattern,
+        row,
+        stop_after: Some(last),
+    });
+}
+
+fn current_pattern_len(app: &App) -> usize {
+    let state = app.state.borrow();
+    state.patterns[state.current_pattern].len()
+}
+
+fn num_channels(app: &App) -> usize {
+    app.state.borrow().num_channels
+}
+
+// Keeps the cursor inside the current pattern, which may be shorter or narrower than before.
+fn clamp_cursor(app: &mut App) {
+    app.cursor_row = app.cursor_row.min(current_pattern_len(app) - 1);
+    app.cursor_channel = app.cursor_channel.min(num_channels(app) - 1);
+}
+
+pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) -> bool {
+    let mut edited = false;
+    clamp_cursor(app);
+    let pattern_len = current_pattern_len(app);
+    let num_channels = num_channels(app);
+
+    // Shift+Up/Down selects a block of rows, moving without Shift drops the selection
+    if let KeyCode::Up | KeyCode::Down = key.code {
+        if key.modifiers.contains(event::KeyModifiers::SHIFT) {
+            app.block_anchor.get_or_insert(app.cursor_row);
+        } else {
+            app.block_anchor = None;
+        }
+    }
+
     match key.code {
         // Load Project (F9) - Open File Dialog
         KeyCode::F(9) => {
@@ -59,9 +151,10 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
                     let path = entry.path();
                     if let Some(ext) = path.extension()
                         && ext == "json"
-                            && let Some(name) = path.file_name() {
-                                app.file_list.push(name.to_string_lossy().into_owned());
-                            }
+                        && let Some(name) = path.file_name()
+                    {
+                        app.file_list.push(name.to_string_lossy().into_owned());
+                    }
                 }
                 app.file_list.sort();
                 if !app.file_list.is_empty() {
@@ -90,12 +183,9 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
                 }
             };
 
-            let (bpm, patterns, instruments) = {
-                let state = app.state.lock().unwrap();
-                (state.bpm, state.patterns.clone(), state.instruments.clone())
-            };
+            let project = Project::from_state(&app.state.borrow());
 
-            if let Err(e) = save_project(&filename, bpm, &patterns, &instruments) {
+            if let Err(e) = save_project(&filename, &project) {
                 app.set_status(format!("Error saving new: {}", e));
             } else {
                 app.set_status(format!("Saved new to {}", filename));
@@ -105,11 +195,8 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
         // Save (F11) - Overwrite current or Save New if none
         KeyCode::F(11) => {
             if let Some(filename) = &app.current_filename {
-                let (bpm, patterns, instruments) = {
-                    let state = app.state.lock().unwrap();
-                    (state.bpm, state.patterns.clone(), state.instruments.clone())
-                };
-                if let Err(e) = save_project(filename, bpm, &patterns, &instruments) {
+                let project = Project::from_state(&app.state.borrow());
+                if let Err(e) = save_project(filename, &project) {
                     app.set_status(format!("Error saving: {}", e));
                 } else {
                     app.set_status(format!("Saved to {}", filename));
@@ -130,12 +217,9 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
                     }
                 };
 
-                let (bpm, patterns, instruments) = {
-                    let state = app.state.lock().unwrap();
-                    (state.bpm, state.patterns.clone(), state.instruments.clone())
-                };
+                let project = Project::from_state(&app.state.borrow());
 
-                if let Err(e) = save_project(&filename, bpm, &patterns, &instruments) {
+                if let Err(e) = save_project(&filename, &project) {
                     app.set_status(format!("Error saving new: {}", e));
                 } else {
                     app.set_status(format!("Saved new to {}", filename));
@@ -143,12 +227,13 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
                 }
             }
         }
-        // Render to WAV (F12)
+        // Render to WAV (F12), Shift+F12 keeps muted / soloed channels as heard
         KeyCode::F(12) => {
-            let state_clone = { app.state.lock().unwrap().clone() };
+            let honour_mute_solo = key.modifiers.contains(event::KeyModifiers::SHIFT);
+            let state_clone = { app.state.borrow().clone() };
             app.set_status("Rendering to output.wav...".to_string());
 
-            if let Err(e) = render_to_wav("output.wav", &state_clone) {
+            if let Err(e) = render_to_wav("output.wav", &state_clone, honour_mute_solo) {
                 app.set_status(format!("Render failed: {}", e));
             } else {
                 app.set_status("Render complete: output.wav".to_string());
@@ -156,91 +241,104 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
         }
 
         // Navigation & Editing
-        KeyCode::Down => {
-            if app.cursor_row < ROWS_PER_PATTERN - 1 {
-                app.cursor_row += 1;
-            }
-        }
-        KeyCode::Up => {
-            if app.cursor_row > 0 {
-                app.cursor_row -= 1;
-            }
-        }
-        KeyCode::Right => {
-            if app.cursor_channel < NUM_CHANNELS - 1 {
-                app.cursor_channel += 1;
-                app.current_instrument_idx = app.cursor_channel;
-            }
-        }
-        KeyCode::Left => {
-            if app.cursor_channel > 0 {
-                app.cursor_channel -= 1;
-                app.current_instrument_idx = app.cursor_channel;
-            }
-        }
-        KeyCode::F(1) => {
-            if app.current_octave > 0 {
-                app.current_octave -= 1;
-            }
-        }
-        KeyCode::F(2) => {
-            if app.current_octave < 8 {
-                app.current_octave += 1;
-            }
-        }
-        KeyCode::F(3) => {
-            if app.edit_step > 0 {
-                app.edit_step -= 1;
-            }
-        }
-        KeyCode::F(4) => {
-            if app.edit_step < 16 {
-                app.edit_step += 1;
+        KeyCode::Down => app.cursor_row = (app.cursor_row + 1).min(pattern_len - 1),
+        KeyCode::Up => app.cursor_row = app.cursor_row.saturating_sub(1),
+        KeyCode::Right => match app.cursor_column {
+            PatternColumn::Note => app.cursor_column = PatternColumn::Instrument,
+            PatternColumn::Instrument => app.cursor_column = PatternColumn::Volume,
+            PatternColumn::Volume => app.cursor_column = PatternColumn::Effect,
+            PatternColumn::Effect => app.cursor_column = PatternColumn::EffectParam,
+            PatternColumn::EffectParam => {
+                if app.cursor_channel < num_channels - 1 {
+                    app.cursor_channel += 1;
+                    app.cursor_column = PatternColumn::Note;
+                }
             }
-        }
-        KeyCode::F(5) => {
-            let current_pattern = {
-                let mut state = app.state.lock().unwrap();
-                if state.current_pattern > 0 {
-                    state.current_pattern -= 1;
+        },
+        KeyCode::Left => match app.cursor_column {
+            PatternColumn::EffectParam => app.cursor_column = PatternColumn::Effect,
+            PatternColumn::Effect => app.cursor_column = PatternColumn::Volume,
+            PatternColumn::Volume => app.cursor_column = PatternColumn::Instrument,
+            PatternColumn::Instrument => app.cursor_column = PatternColumn::Note,
+            PatternColumn::Note => {
+                if app.cursor_channel > 0 {
+                    app.cursor_channel -= 1;
+                    app.cursor_column = PatternColumn::EffectParam;
                 }
-                state.current_pattern
-            };
-            app.set_status(format!("Pattern: {}", current_pattern));
-        }
-        KeyCode::F(6) => {
-            let current_pattern = {
-                let mut state = app.state.lock().unwrap();
-                if state.current_pattern < 255 {
-                    // Arbitrary limit
-                    state.current_pattern += 1;
-                    if state.current_pattern >= state.patterns.len() {
-                        state.patterns.push(Pattern::default());
+            }
+        },
+        KeyCode::F(1) => app.current_octave = app.current_octave.saturating_sub(1),
+        KeyCode::F(2) => app.current_octave = (app.current_octave + 1).min(8),
+        KeyCode::F(3) => app.edit_step = app.edit_step.saturating_sub(1),
+        KeyCode::F(4) => app.edit_step = (app.edit_step + 1).min(16),
+        KeyCode::F(c @ (5 | 6)) => {
+            let (pattern, is_playing, message) = {
+                let mut state = app.state.borrow_mut();
+                // While playing, steps on from the pattern already waiting to take over
+                let base = match state.queued_pattern {
+                    Some(pattern) if state.is_playing => pattern,
+                    _ => state.current_pattern,
+                };
+                // Arbitrary limit of 256 patterns
+                let pattern = if c == 5 {
+                    base.saturating_sub(1)
+                } else {
+                    (base + 1).min(255)
+                };
+                if pattern >= state.patterns.len() {
+                    let new_pattern = state.new_pattern();
+                    state.patterns.push(new_pattern);
+                    // The engine needs the new pattern before it can switch to it
+                    if state.is_playing {
+                        app.engine.send_song(&state);
+                    } else {
+                        edited = true;
                     }
                 }
-                state.current_pattern
+                let message = if !state.is_playing {
+                    state.current_pattern = pattern;
+                    format!("Pattern: {}", pattern)
+                } else if state.pattern_launch != PatternLaunch::Immediate {
+                    state.queued_pattern = Some(pattern);
+                    format!(
+                        "Next Pattern: {} ({})",
+                        pattern,
+                        state.pattern_launch.name()
+                    )
+                } else {
+                    format!("Pattern: {}", pattern)
+                };
+                (pattern, state.is_playing, message)
             };
-            app.set_status(format!("Pattern: {}", current_pattern));
+            if is_playing {
+                app.engine.send(EngineCommand::SwitchPattern(pattern));
+            }
+            app.set_status(message);
+        }
+        KeyCode::Char('K') => {
+            let launch = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                state.pattern_launch = state.pattern_launch.next();
+                state.pattern_launch
+            };
+            app.set_status(format!("Pattern Launch: {}", launch.name()));
         }
         KeyCode::F(7) => {
-            let mut state = app.state.lock().unwrap();
-            if state.bpm > 10.0 {
-                let sample_rate = (state.samples_per_tick as f32 * state.bpm * 4.0) / 60.0;
-                state.bpm -= 5.0;
-                state.samples_per_tick = ((sample_rate * 60.0) / (state.bpm * 4.0)) as usize;
-            }
+            let bpm = app.state.borrow().bpm;
+            set_bpm(app, (bpm - 5.0).max(MIN_BPM));
         }
         KeyCode::F(8) => {
-            let mut state = app.state.lock().unwrap();
-            if state.bpm < 300.0 {
-                let sample_rate = (state.samples_per_tick as f32 * state.bpm * 4.0) / 60.0;
-                state.bpm += 5.0;
-                state.samples_per_tick = ((sample_rate * 60.0) / (state.bpm * 4.0)) as usize;
-            }
+            let bpm = app.state.borrow().bpm;
+            set_bpm(app, (bpm + 5.0).min(MAX_BPM));
+        }
+        KeyCode::Char('T') => {
+            app.bpm_entry = Some(String::new());
         }
         KeyCode::Char('p') => {
             let play_mode = {
-                let mut state = app.state.lock().unwrap();
+                let mut state = app.state.borrow_mut();
+                edited = true;
                 state.play_mode = match state.play_mode {
                     PlayMode::Pattern => PlayMode::Song,
                     PlayMode::Song => PlayMode::Pattern,
@@ -251,12 +349,11 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
         }
         KeyCode::Char('n') => {
             let (new_idx, total) = {
-                let mut state = app.state.lock().unwrap();
+                let mut state = app.state.borrow_mut();
+                edited = true;
                 let current_pattern_idx = state.current_pattern;
                 let current_pattern = state.patterns[current_pattern_idx].clone();
-                state
-                    .patterns
-                    .insert(current_pattern_idx + 1, current_pattern);
+                state.insert_pattern(current_pattern_idx + 1, current_pattern);
                 state.current_pattern += 1;
                 (state.current_pattern, state.patterns.len())
             };
@@ -264,34 +361,253 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
         }
         KeyCode::Char('x') => {
             let (new_idx, total) = {
-                let mut state = app.state.lock().unwrap();
+                let mut state = app.state.borrow_mut();
+                edited = true;
                 let current_pattern_idx = state.current_pattern;
-                if state.patterns.len() > 1 {
-                    state.patterns.remove(current_pattern_idx);
-                    if state.current_pattern >= state.patterns.len() {
-                        state.current_pattern = state.patterns.len() - 1;
-                    }
-                    (state.current_pattern, state.patterns.len())
-                } else {
-                    (state.current_pattern, state.patterns.len())
-                }
+                state.remove_pattern(current_pattern_idx);
+                (state.current_pattern, state.patterns.len())
             };
             app.set_status(format!(
                 "Deleted Pattern. Current: {} (Total: {})",
                 new_idx, total
             ));
         }
+        KeyCode::Char('M') => {
+            let muted = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                state.toggle_mute(app.cursor_channel);
+                state.channel_settings[app.cursor_channel].muted
+            };
+            let action = if muted { "muted" } else { "unmuted" };
+            app.set_status(format!("Channel {:02X} {}", app.cursor_channel, action));
+        }
+        KeyCode::Char('S') => {
+            let solo = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                state.toggle_solo(app.cursor_channel);
+                state.channel_settings[app.cursor_channel].solo
+            };
+            let action = if solo { "soloed" } else { "unsoloed" };
+            app.set_status(format!("Channel {:02X} {}", app.cursor_channel, action));
+        }
+        KeyCode::Char('V') => {
+            let voice_mode = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                state.cycle_voice_mode(app.cursor_channel);
+                state.channel_settings[app.cursor_channel].voice_mode
+            };
+            app.set_status(format!(
+                "Channel {:02X} Voice Mode: {:?}",
+                app.cursor_channel, voice_mode
+            ));
+        }
+        KeyCode::Char('L') => {
+            let voice_limit = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                let settings = &mut state.channel_settings[app.cursor_channel];
+                settings.voice_limit = if settings.voice_limit >= MAX_VOICES {
+                    1
+                } else {
+                    settings.voice_limit * 2
+                };
+                settings.voice_limit
+            };
+            app.set_status(format!(
+                "Channel {:02X} Voice Limit: {}",
+                app.cursor_channel, voice_limit
+            ));
+        }
+        KeyCode::Char('O') => {
+            let voice_stealing = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                let settings = &mut state.channel_settings[app.cursor_channel];
+                settings.voice_stealing = match settings.voice_stealing {
+                    VoiceStealing::Oldest => VoiceStealing::Quietest,
+                    VoiceStealing::Quietest => VoiceStealing::Oldest,
+                };
+                settings.voice_stealing
+            };
+            app.set_status(format!(
+                "Channel {:02X} Voice Stealing: {:?}",
+                app.cursor_channel, voice_stealing
+            ));
+        }
+        KeyCode::Char(c @ ('(' | ')')) => {
+            let pan = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                let settings = &mut state.channel_settings[app.cursor_channel];
+                let step = if c == '(' { -0.1 } else { 0.1 };
+                settings.pan = ((settings.pan + step) * 10.0).round().clamp(-10.0, 10.0) / 10.0;
+                settings.pan
+            };
+            app.set_status(format!(
+                "Channel {:02X} Pan: {:+.1}",
+                app.cursor_channel, pan
+            ));
+        }
+        KeyCode::Char('U') => {
+            app.state.borrow_mut().clear_mute_solo();
+            edited = true;
+            app.set_status("Cleared mute / solo".to_string());
+        }
+        KeyCode::Esc => {
+            app.block_anchor = None;
+        }
+        KeyCode::Char('R') => {
+            play_rows(app, app.cursor_row, app.cursor_row);
+        }
+        KeyCode::Char('P') => match app.selected_rows() {
+            Some((first, last)) => play_rows(app, first, last),
+            None => app.set_status("Select rows with Shift+Up/Down first".to_string()),
+        },
+        KeyCode::Char('W') => {
+            let selection = app.selected_rows();
+            let message = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                if let Some((first, last)) = selection {
+                    state.loop_rows = Some((first, last));
+                    format!("Looping rows {:02X}-{:02X}", first, last)
+                } else if state.loop_rows.take().is_some() {
+                    "Row loop off".to_string()
+                } else {
+                    "Select rows with Shift+Up/Down to loop them".to_string()
+                }
+            };
+            app.set_status(message);
+        }
+        KeyCode::Char(c @ ('<' | '>')) => {
+            let new_count = if c == '<' {
+                num_channels.saturating_sub(1).max(MIN_CHANNELS)
+            } else {
+                (num_channels + 1).min(MAX_CHANNELS)
+            };
+            // Removing a channel drops its notes, so only empty channels go
+            if new_count < num_channels && !app.state.borrow().is_channel_empty(new_count) {
+                app.set_status(format!(
+                    "Channel {:02X} has notes, clear it before removing it",
+                    new_count
+                ));
+            } else {
+                app.state.borrow_mut().set_num_channels(new_count);
+                edited = true;
+                app.set_status(format!("Channels: {}", new_count));
+            }
+        }
+        KeyCode::Char(c @ ('[' | ']' | '{' | '}')) => {
+            let new_len = {
+                let mut state = app.state.borrow_mut();
+                edited = true;
+                let pattern_idx = state.current_pattern;
+                let pattern = &mut state.patterns[pattern_idx];
+                match c {
+                    '[' => pattern.resize(pattern_len - 1),
+                    ']' => pattern.resize(pattern_len + 1),
+                    '{' => pattern.halve(),
+                    _ => pattern.double(),
+                }
+                pattern.len()
+            };
+            app.set_status(format!("Pattern Length: {} rows", new_len));
+        }
         KeyCode::Backspace | KeyCode::Delete | KeyCode::Char('.') => {
-            let mut state = app.state.lock().unwrap();
+            let mut state = app.state.borrow_mut();
+            edited = true;
             let pattern_idx = state.current_pattern;
-            state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel].key = 0;
-            state.preview_request = Some((app.cursor_channel, 0));
+            let note = &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+            match app.cursor_column {
+                PatternColumn::Note => {
+                    *note = Note::default();
+                    app.engine.send(EngineCommand::Preview {
+                        channel: app.cursor_channel,
+                        note: Note::default(),
+                    });
+                }
+                PatternColumn::Instrument => note.instrument = None,
+                PatternColumn::Volume => note.volume = None,
+                PatternColumn::Effect | PatternColumn::EffectParam => {
+                    note.effect = 0;
+                    note.effect_param = 0;
+                }
+            }
 
             if app.edit_step > 0 {
-                app.cursor_row = (app.cursor_row + app.edit_step).min(ROWS_PER_PATTERN - 1);
+                app.cursor_row = (app.cursor_row + app.edit_step).min(pattern_len - 1);
             }
         }
-        KeyCode::Char(c) => {
+        KeyCode::Char(c)
+            if app.cursor_column == PatternColumn::Instrument && c.is_ascii_hexdigit() =>
+        {
+            let digit = c.to_digit(16).unwrap() as usize;
+            let mut state = app.state.borrow_mut();
+            edited = true;
+            let pattern_idx = state.current_pattern;
+            let note = &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+
+            let current = note.instrument.map_or(0, usize::from);
+            let mut new_idx = (current % 16) * 16 + digit;
+            if new_idx >= NUM_INSTRUMENTS {
+                new_idx = digit;
+            }
+            note.instrument = Some(new_idx as u8);
+            app.current_instrument_idx = new_idx;
+        }
+        KeyCode::Char(c) if app.cursor_column == PatternColumn::Volume && c.is_ascii_hexdigit() => {
+            let digit = c.to_digit(16).unwrap() as u8;
+            let mut state = app.state.borrow_mut();
+            edited = true;
+            let pattern_idx = state.current_pattern;
+            let note = &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+
+            let current = note.volume.unwrap_or(0);
+            let mut new_vol = (current % 16) * 16 + digit;
+            if new_vol > MAX_VOLUME {
+                new_vol = digit;
+            }
+            note.volume = Some(new_vol);
+        }
+        KeyCode::Char(c) if app.cursor_column == PatternColumn::Effect && c.is_ascii_hexdigit() => {
+            let mut state = app.state.borrow_mut();
+            edited = true;
+            let pattern_idx = state.current_pattern;
+            state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel].effect =
+                c.to_digit(16).unwrap() as u8;
+        }
+        KeyCode::Char(c)
+            if app.cursor_column == PatternColumn::EffectParam && c.is_ascii_hexdigit() =>
+        {
+            let digit = c.to_digit(16).unwrap() as u8;
+            let mut state = app.state.borrow_mut();
+            edited = true;
+            let pattern_idx = state.current_pattern;
+            let note = &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+            note.effect_param = (note.effect_param % 16) * 16 + digit;
+        }
+        KeyCode::Char(c @ ('=' | '^')) if app.cursor_column == PatternColumn::Note => {
+            let mut state = app.state.borrow_mut();
+            edited = true;
+            let pattern_idx = state.current_pattern;
+            let cell = &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+            *cell = Note {
+                key: if c == '=' { NOTE_OFF } else { NOTE_CUT },
+                ..Note::default()
+            };
+            app.engine.send(EngineCommand::Preview {
+                channel: app.cursor_channel,
+                note: Note::default(),
+            });
+
+            if app.edit_step > 0 {
+                app.cursor_row = (app.cursor_row + app.edit_step).min(pattern_len - 1);
+            }
+        }
+        KeyCode::Char(c) if app.cursor_column == PatternColumn::Note => {
             let base_note = match c {
                 'z' => Some(0),
                 's' => Some(1),
@@ -310,37 +626,477 @@ pub fn handle_pattern_input(key: event::KeyEvent, app: &mut App) {
             };
 
             if let Some(base) = base_note {
-                let mut state = app.state.lock().unwrap();
+                let mut state = app.state.borrow_mut();
+                edited = true;
                 let midi_note = base + (app.current_octave + 1) * 12;
                 if midi_note < 128 {
                     let pattern_idx = state.current_pattern;
-                    state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel].key =
-                        midi_note;
-                    state.preview_request = Some((app.cursor_channel, midi_note));
+                    let cell =
+                        &mut state.patterns[pattern_idx].rows[app.cursor_row][app.cursor_channel];
+                    cell.key = midi_note;
+                    cell.instrument = Some(app.current_instrument_idx as u8);
+                    let note = *cell;
+                    app.engine.send(EngineCommand::Preview {
+                        channel: app.cursor_channel,
+                        note,
+                    });
                 }
 
                 if app.edit_step > 0 {
-                    app.cursor_row = (app.cursor_row + app.edit_step).min(ROWS_PER_PATTERN - 1);
+                    app.cursor_row = (app.cursor_row + app.edit_step).min(pattern_len - 1);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    clamp_cursor(app);
+    edited
+}
+
+pub fn handle_order_input(key: event::KeyEvent, app: &mut App) -> bool {
+    let shift = key.modifiers.contains(event::KeyModifiers::SHIFT);
+    let mut state = app.state.borrow_mut();
+    app.order_cursor = app.order_cursor.min(state.order.len() - 1);
+    let pos = app.order_cursor;
+    let mut edited = false;
+
+    match key.code {
+        // Move entry
+        KeyCode::Up if shift && pos > 0 => {
+            state.order.swap(pos, pos - 1);
+            app.order_cursor -= 1;
+            edited = true;
+        }
+        KeyCode::Down if shift && pos < state.order.len() - 1 => {
+            state.order.swap(pos, pos + 1);
+            app.order_cursor += 1;
+            edited = true;
+        }
+        KeyCode::Up if !shift && pos > 0 => {
+            app.order_cursor -= 1;
+        }
+        KeyCode::Down if !shift && pos < state.order.len() - 1 => {
+            app.order_cursor += 1;
+        }
+        // Change pattern at position
+        KeyCode::Left | KeyCode::Char('-') if state.order[pos] > 0 => {
+            state.order[pos] -= 1;
+            edited = true;
+        }
+        KeyCode::Right | KeyCode::Char('+') if state.order[pos] < state.patterns.len() - 1 => {
+            state.order[pos] += 1;
+            edited = true;
+        }
+        KeyCode::Insert | KeyCode::Char('i') => {
+            let pattern_idx = state.order[pos];
+            state.order.insert(pos + 1, pattern_idx);
+            app.order_cursor += 1;
+            edited = true;
+        }
+        KeyCode::Delete | KeyCode::Backspace if state.order.len() > 1 => {
+            state.order.remove(pos);
+            edited = true;
+            let last_pos = state.order.len() - 1;
+            app.order_cursor = app.order_cursor.min(last_pos);
+            state.current_order = state.current_order.min(last_pos);
+            state.loop_orders = state
+                .loop_orders
+                .map(|(first, last)| (first.min(last_pos), last.min(last_pos)));
+        }
+        // Song loop from / to this position
+        KeyCode::Char('[') => {
+            let last = state.loop_orders.map_or(pos, |(_, last)| last.max(pos));
+            state.loop_orders = Some((pos, last));
+            edited = true;
+        }
+        KeyCode::Char(']') => {
+            let first = state.loop_orders.map_or(pos, |(first, _)| first.min(pos));
+            state.loop_orders = Some((first, pos));
+            edited = true;
+        }
+        KeyCode::Char('l' | 'L') => {
+            state.loop_orders = None;
+            edited = true;
+        }
+        // Edit the pattern at this position
+        KeyCode::Enter => {
+            state.current_order = pos;
+            state.current_pattern = state.order[pos];
+            drop(state);
+            app.current_view = View::Pattern;
+            app.order_list_state.select(Some(app.order_cursor));
+            return false;
+        }
+        _ => {}
+    }
+    app.order_list_state.select(Some(app.order_cursor));
+    edited
+}
+
+// Faders move in whole dB steps and drop to silence below -60 dB.
+fn step_fader(gain: f32, db: f32) -> f32 {
+    const FLOOR_DB: f32 = -60.0;
+    if gain <= 0.0 {
+        return if db > 0.0 {
+            10.0f32.powf(FLOOR_DB / 20.0)
+        } else {
+            0.0
+        };
+    }
+    let new_db = (20.0 * gain.log10() + db).round();
+    if new_db < FLOOR_DB {
+        0.0
+    } else {
+        10.0f32.powf(new_db / 20.0).min(MAX_FADER_GAIN)
+    }
+}
+
+// Steps a 0..=max amount by 0.05.
+fn step_amount(amount: f32, step: f32, max: f32) -> f32 {
+    ((amount + step) * 20.0).round().clamp(0.0, max * 20.0) / 20.0
+}
+
+// Rows 0..num_channels are the channel strips, followed by the reverb and delay returns and
+// the master bus as the last row.
+pub fn handle_mixer_input(key: event::KeyEvent, app: &mut App) -> bool {
+    let mut state = app.state.borrow_mut();
+    let num_channels = state.num_channels;
+    let reverb_row = num_channels;
+    let delay_row = num_channels + 1;
+    let master_row = num_channels + 2;
+    app.mixer_cursor = app.mixer_cursor.min(master_row);
+    let row = app.mixer_cursor;
+    let is_channel = row < num_channels;
+    let mut edited = false;
+
+    match key.code {
+        KeyCode::Up if row > 0 => {
+            app.mixer_cursor -= 1;
+        }
+        KeyCode::Down if row < master_row => {
+            app.mixer_cursor += 1;
+        }
+        KeyCode::Left | KeyCode::Right | KeyCode::Char('-' | '+' | '0') => {
+            let volume = match row {
+                r if r == reverb_row => &mut state.sends.reverb_return,
+                r if r == delay_row => &mut state.sends.delay_return,
+                r if r == master_row => &mut state.master_volume,
+                _ => &mut state.channel_settings[row].volume,
+            };
+            *volume = match key.code {
+                KeyCode::Left | KeyCode::Char('-') => step_fader(*volume, -1.0),
+                KeyCode::Right | KeyCode::Char('+') => step_fader(*volume, 1.0),
+                _ => 1.0,
+            };
+            edited = true;
+        }
+        KeyCode::Char(c @ ('(' | ')')) if is_channel => {
+            let settings = &mut state.channel_settings[row];
+            let step = if c == '(' { -0.1 } else { 0.1 };
+            settings.pan = ((settings.pan + step) * 10.0).round().clamp(-10.0, 10.0) / 10.0;
+            edited = true;
+        }
+        KeyCode::Char(c @ ('[' | ']' | '{' | '}')) if is_channel => {
+            let settings = &mut state.channel_settings[row];
+            let step = if c == '[' || c == '{' { -0.05 } else { 0.05 };
+            let send = if c == '[' || c == ']' {
+                &mut settings.reverb_send
+            } else {
+                &mut settings.delay_send
+            };
+            *send = step_amount(*send, step, 1.0);
+            edited = true;
+        }
+        KeyCode::Char(c @ ('[' | ']' | '{' | '}')) if row == reverb_row => {
+            let sends = &mut state.sends;
+            let step = if c == '[' || c == '{' { -0.05 } else { 0.05 };
+            if c == '[' || c == ']' {
+                sends.reverb_room_size = step_amount(sends.reverb_room_size, step, 1.0);
+            } else {
+                sends.reverb_damping = step_amount(sends.reverb_damping, step, 1.0);
+            }
+            edited = true;
+        }
+        KeyCode::Char(c @ ('[' | ']' | '<' | '>')) if row == delay_row => {
+            let sends = &mut state.sends;
+            let step = if c == '[' || c == '<' { -1.0 } else { 1.0 };
+            let rows = if c == '[' || c == ']' {
+                &mut sends.delay_rows_left
+            } else {
+                &mut sends.delay_rows_right
+            };
+            *rows = (*rows + step).clamp(1.0, MAX_DELAY_ROWS);
+            edited = true;
+        }
+        KeyCode::Char(c @ ('{' | '}')) if row == delay_row => {
+            let step = if c == '{' { -0.05 } else { 0.05 };
+            state.sends.delay_feedback = step_amount(state.sends.delay_feedback, step, 0.95);
+            edited = true;
+        }
+        KeyCode::Char('m' | 'M') if is_channel => {
+            state.toggle_mute(row);
+            edited = true;
+        }
+        KeyCode::Char('s' | 'S') if is_channel => {
+            state.toggle_solo(row);
+            edited = true;
+        }
+        KeyCode::Char('u' | 'U') => {
+            state.clear_mute_solo();
+            edited = true;
+        }
+        KeyCode::Enter if is_channel => {
+            app.cursor_channel = row;
+            app.strip_param_idx = 0;
+            app.current_view = View::Strip;
+        }
+        _ => {}
+    }
+    app.mixer_table_state.select(Some(app.mixer_cursor));
+    edited
+}
+
+// Finds the insert and its parameter for a row of the channel strip.
+fn insert_at_param(inserts: &[InsertConfig], param_idx: usize) -> Option<(usize, usize)> {
+    let mut first_param = 0;
+    for (i, insert) in inserts.iter().enumerate() {
+        let count = insert.num_params();
+        if param_idx < first_param + count {
+            return Some((i, param_idx - first_param));
+        }
+        first_param += count;
+    }
+    None
+}
+
+pub fn handle_strip_input(key: event::KeyEvent, app: &mut App) -> bool {
+    clamp_cursor(app);
+    let num_channels = num_channels(app);
+    let mut state = app.state.borrow_mut();
+    let inserts = &mut state.channel_settings[app.cursor_channel].inserts;
+    let total_params: usize = inserts.iter().map(|insert| insert.num_params()).sum();
+    let selected = insert_at_param(inserts, app.strip_param_idx);
+    let mut edited = false;
+
+    match key.code {
+        KeyCode::Left if app.cursor_channel > 0 => {
+            app.cursor_channel -= 1;
+            app.strip_param_idx = 0;
+        }
+        KeyCode::Right if app.cursor_channel < num_channels - 1 => {
+            app.cursor_channel += 1;
+            app.strip_param_idx = 0;
+        }
+        KeyCode::Up if app.strip_param_idx > 0 => {
+            app.strip_param_idx -= 1;
+        }
+        KeyCode::Down if app.strip_param_idx + 1 < total_params => {
+            app.strip_param_idx += 1;
+        }
+        KeyCode::Char(c @ '1'..='4') if inserts.len() < MAX_INSERTS => {
+            inserts.push(match c {
+                '1' => InsertConfig::default_filter(),
+                '2' => InsertConfig::default_distortion(),
+                '3' => InsertConfig::default_chorus(),
+                _ => InsertConfig::default_compressor(),
+            });
+            // Select the new insert
+            app.strip_param_idx = total_params;
+            edited = true;
+        }
+        KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('x' | 'X') => {
+            if let Some((idx, _)) = selected {
+                inserts.remove(idx);
+                let total_params: usize = inserts.iter().map(|insert| insert.num_params()).sum();
+                app.strip_param_idx = app.strip_param_idx.min(total_params.saturating_sub(1));
+                edited = true;
+            }
+        }
+        // Move the selected insert earlier / later in the chain
+        KeyCode::Char(c @ ('[' | ']')) => {
+            if let Some((idx, param)) = selected {
+                let target = if c == '[' {
+                    idx.checked_sub(1)
+                } else {
+                    Some(idx + 1).filter(|&target| target < inserts.len())
+                };
+                if let Some(target) = target {
+                    inserts.swap(idx, target);
+                    let first_param: usize = inserts[..target]
+                        .iter()
+                        .map(|insert| insert.num_params())
+                        .sum();
+                    app.strip_param_idx = first_param + param;
+                    edited = true;
                 }
             }
         }
+        KeyCode::Char(c @ ('+' | '-')) => {
+            if let Some((idx, param)) = selected {
+                let dir = if c == '+' { 1.0 } else { -1.0 };
+                change_insert_param(&mut inserts[idx], param, dir);
+                edited = true;
+            }
+        }
         _ => {}
     }
+    app.strip_table_state.select(Some(app.strip_param_idx));
+    edited
 }
 
-pub fn handle_instrument_input(code: KeyCode, app: &mut App) {
+fn change_insert_param(insert: &mut InsertConfig, param: usize, dir: f32) {
+    match insert {
+        InsertConfig::Filter { mode, cutoff, q } => match param {
+            0 => {
+                if dir > 0.0 {
+                    *mode = match mode {
+                        FilterMode::LowPass => FilterMode::HighPass,
+                        FilterMode::HighPass => FilterMode::BandPass,
+                        FilterMode::BandPass => FilterMode::Notch,
+                        FilterMode::Notch => FilterMode::LowPass,
+                    };
+                } else {
+                    *mode = match mode {
+                        FilterMode::LowPass => FilterMode::Notch,
+                        FilterMode::HighPass => FilterMode::LowPass,
+                        FilterMode::BandPass => FilterMode::HighPass,
+                        FilterMode::Notch => FilterMode::BandPass,
+                    };
+                }
+            }
+            1 => *cutoff = (*cutoff + dir * 100.0).clamp(20.0, 20000.0),
+            _ => *q = (*q + dir * 0.1).clamp(0.1, 10.0),
+        },
+        InsertConfig::Distortion { mode, drive, mix } => match param {
+            0 => {
+                if dir > 0.0 {
+                    *mode = match mode {
+                        DistortionMode::SoftClip => DistortionMode::HardClip,
+                        DistortionMode::HardClip => DistortionMode::Foldback,
+                        DistortionMode::Foldback => DistortionMode::SoftClip,
+                    };
+                } else {
+                    *mode = match mode {
+                        DistortionMode::SoftClip => DistortionMode::Foldback,
+                        DistortionMode::HardClip => DistortionMode::SoftClip,
+                        DistortionMode::Foldback => DistortionMode::HardClip,
+                    };
+                }
+            }
+            1 => *drive = (*drive + dir * 0.5).clamp(1.0, 50.0),
+            _ => *mix = (*mix + dir * 0.05).clamp(0.0, 1.0),
+        },
+        InsertConfig::Chorus {
+            depth,
+            feedback,
+            mix,
+        } => match param {
+            0 => *depth = (*depth + dir * 0.5).clamp(0.0, 10.0),
+            1 => *feedback = (*feedback + dir * 0.05).clamp(0.0, 0.9),
+            _ => *mix = (*mix + dir * 0.05).clamp(0.0, 1.0),
+        },
+        InsertConfig::Compressor {
+            threshold,
+            ratio,
+            attack,
+            release,
+            makeup,
+        } => match param {
+            0 => *threshold = (*threshold + dir).clamp(-60.0, 0.0),
+            1 => *ratio = (*ratio + dir * 0.5).clamp(1.0, 20.0),
+            2 => *attack = (*attack + dir).clamp(1.0, 200.0),
+            3 => *release = (*release + dir * 10.0).clamp(10.0, 2000.0),
+            _ => *makeup = (*makeup + dir).clamp(0.0, 24.0),
+        },
+    }
+}
+
+// Steps a groove offset by 0.05 within min..=MAX_GROOVE_OFFSET.
+fn step_offset(offset: f32, step: f32, min: f32) -> f32 {
+    ((offset + step) * 20.0)
+        .round()
+        .clamp(min * 20.0, MAX_GROOVE_OFFSET * 20.0)
+        / 20.0
+}
+
+// Row 0 is the swing amount, the template's rows follow. Edits go to the current pattern's
+// groove if it has one, otherwise to the project's.
+pub fn handle_groove_input(key: event::KeyEvent, app: &mut App) -> bool {
+    if let KeyCode::Char('g' | 'G') = key.code {
+        let message = {
+            let mut state = app.state.borrow_mut();
+            let pattern_idx = state.current_pattern;
+            if state.patterns[pattern_idx].groove.is_some() {
+                state.patterns[pattern_idx].groove = None;
+                format!("Pattern {:02X} uses the project groove", pattern_idx)
+            } else {
+                state.patterns[pattern_idx].groove = Some(state.groove.clone());
+                format!("Pattern {:02X} has its own groove", pattern_idx)
+            }
+        };
+        app.set_status(message);
+        return true;
+    }
+
+    let mut state = app.state.borrow_mut();
+    let pattern_idx = state.current_pattern;
+    let groove = state.groove_mut(pattern_idx);
+    app.groove_cursor = app.groove_cursor.min(groove.offsets.len());
+    let row = app.groove_cursor;
+    let mut edited = false;
+
+    match key.code {
+        KeyCode::Up if row > 0 => {
+            app.groove_cursor -= 1;
+        }
+        KeyCode::Down if row < groove.offsets.len() => {
+            app.groove_cursor += 1;
+        }
+        KeyCode::Left | KeyCode::Right | KeyCode::Char('-' | '+' | '0') => {
+            let (value, min) = if row == 0 {
+                (&mut groove.swing, 0.0)
+            } else {
+                (&mut groove.offsets[row - 1], -MAX_GROOVE_OFFSET)
+            };
+            *value = match key.code {
+                KeyCode::Left | KeyCode::Char('-') => step_offset(*value, -0.05, min),
+                KeyCode::Right | KeyCode::Char('+') => step_offset(*value, 0.05, min),
+                _ => 0.0,
+            };
+            edited = true;
+        }
+        KeyCode::Char(c @ ('<' | '>')) => {
+            let current = GROOVE_LENGTHS
+                .iter()
+                .position(|&len| len >= groove.offsets.len())
+                .unwrap_or(GROOVE_LENGTHS.len() - 1);
+            let next = if c == '<' {
+                current.saturating_sub(1)
+            } else {
+                (current + 1).min(GROOVE_LENGTHS.len() - 1)
+            };
+            groove.set_cycle_length(GROOVE_LENGTHS[next]);
+            app.groove_cursor = app.groove_cursor.min(GROOVE_LENGTHS[next]);
+            edited = true;
+        }
+        _ => {}
+    }
+    app.groove_table_state.select(Some(app.groove_cursor));
+    edited
+}
+
+pub fn handle_instrument_input(code: KeyCode, app: &mut App) -> bool {
+    let mut edited = false;
     match app.inst_focus {
         InstrumentFocus::List => {
             match code {
                 KeyCode::Up => {
-                    if app.current_instrument_idx > 0 {
-                        app.current_instrument_idx -= 1;
-                    }
+                    app.current_instrument_idx = app.current_instrument_idx.saturating_sub(1);
                 }
                 KeyCode::Down => {
-                    if app.current_instrument_idx < NUM_INSTRUMENTS - 1 {
-                        app.current_instrument_idx += 1;
-                    }
+                    app.current_instrument_idx =
+                        (app.current_instrument_idx + 1).min(NUM_INSTRUMENTS - 1);
                 }
                 KeyCode::Right | KeyCode::Enter => {
                     app.inst_focus = InstrumentFocus::Params;
@@ -363,13 +1119,9 @@ pub fn handle_instrument_input(code: KeyCode, app: &mut App) {
         }
         InstrumentFocus::Params => {
             match code {
-                KeyCode::Up => {
-                    if app.param_idx > 0 {
-                        app.param_idx -= 1;
-                    }
-                }
+                KeyCode::Up => app.param_idx = app.param_idx.saturating_sub(1),
                 KeyCode::Down => {
-                    let state = app.state.lock().unwrap();
+                    let state = app.state.borrow();
                     let inst = &state.instruments[app.current_instrument_idx];
                     let total_params = count_params(inst);
                     if app.param_idx < total_params - 1 {
@@ -379,30 +1131,87 @@ pub fn handle_instrument_input(code: KeyCode, app: &mut App) {
                 KeyCode::Left | KeyCode::Esc => {
                     app.inst_focus = InstrumentFocus::List;
                 }
-                KeyCode::Char('+') => change_module_param(app, 1.0),
-                KeyCode::Char('-') => change_module_param(app, -1.0),
+                KeyCode::Char('+') => {
+                    change_module_param(app, 1.0);
+                    edited = true;
+                }
+                KeyCode::Char('-') => {
+                    change_module_param(app, -1.0);
+                    edited = true;
+                }
+                // Oscillators go after the last one, so they layer before filters and envelopes
+                KeyCode::Char('o' | 'O') => {
+                    let mut state = app.state.borrow_mut();
+                    let inst = &mut state.instruments[app.current_instrument_idx];
+                    let idx = inst
+                        .modules
+                        .iter()
+                        .rposition(|module| matches!(module, ModuleConfig::Oscillator { .. }))
+                        .map_or(0, |idx| idx + 1);
+                    inst.modules.insert(idx, ModuleConfig::default_oscillator());
+                    edited = true;
+                    // Select the new oscillator
+                    app.param_idx = inst.modules[..idx]
+                        .iter()
+                        .map(|module| module.num_params())
+                        .sum();
+                }
+                KeyCode::Char('l' | 'L') => {
+                    let mut state = app.state.borrow_mut();
+                    let inst = &mut state.instruments[app.current_instrument_idx];
+                    // Select the new LFO
+                    app.param_idx = count_params(inst);
+                    inst.modules.push(ModuleConfig::default_lfo());
+                    edited = true;
+                }
+                KeyCode::Delete | KeyCode::Backspace | KeyCode::Char('x' | 'X') => {
+                    let mut state = app.state.borrow_mut();
+                    let inst = &mut state.instruments[app.current_instrument_idx];
+                    let oscillators = inst
+                        .modules
+                        .iter()
+                        .filter(|module| matches!(module, ModuleConfig::Oscillator { .. }))
+                        .count();
+                    // LFOs and extra oscillators can be removed
+                    let removable = |module: &ModuleConfig| match module {
+                        ModuleConfig::Oscillator { .. } => oscillators > 1,
+                        ModuleConfig::Lfo { .. } => true,
+                        _ => false,
+                    };
+                    if let Some(idx) = module_at_param(inst, app.param_idx)
+                        && removable(&inst.modules[idx])
+                    {
+                        inst.modules.remove(idx);
+                        app.param_idx = app.param_idx.min(count_params(inst) - 1);
+                        edited = true;
+                    }
+                }
                 _ => {}
             }
             app.param_table_state.select(Some(app.param_idx));
         }
     }
+    edited
 }
 
 fn count_params(inst: &crate::core::Instrument) -> usize {
-    let mut count = 0;
-    for module in &inst.modules {
-        count += match module {
-            ModuleConfig::Oscillator { .. } => 3,
-            ModuleConfig::Filter { .. } => 2,
-            ModuleConfig::Adsr { .. } => 4,
-            ModuleConfig::Gain { .. } => 1,
-        };
+    inst.modules.iter().map(|module| module.num_params()).sum()
+}
+
+// Finds the module a row of the parameter table belongs to.
+fn module_at_param(inst: &crate::core::Instrument, param_idx: usize) -> Option<usize> {
+    let mut first_param = 0;
+    for (i, module) in inst.modules.iter().enumerate() {
+        first_param += module.num_params();
+        if param_idx < first_param {
+            return Some(i);
+        }
     }
-    count
+    None
 }
 
 fn change_module_param(app: &mut App, dir: f32) {
-    let mut state = app.state.lock().unwrap();
+    let mut state = app.state.borrow_mut();
     let inst = &mut state.instruments[app.current_instrument_idx];
 
     let mut current_idx = 0;
@@ -410,9 +1219,13 @@ fn change_module_param(app: &mut App, dir: f32) {
         match module {
             ModuleConfig::Oscillator {
                 waveform,
+                pitch_offset,
+                detune,
                 pitch_env_amount,
                 pitch_env_decay,
-                ..
+                level,
+                unison,
+                unison_spread,
             } => {
                 if current_idx == app.param_idx {
                     if dir > 0.0 {
@@ -435,6 +1248,31 @@ fn change_module_param(app: &mut App, dir: f32) {
                     return;
                 }
                 current_idx += 1;
+                if current_idx == app.param_idx {
+                    *pitch_offset = (*pitch_offset + dir).clamp(-48.0, 48.0);
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *detune = (*detune + dir).clamp(-100.0, 100.0);
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *level = (*level + dir * 0.05).clamp(0.0, 1.0);
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *unison = (*unison as f32 + dir).clamp(1.0, MAX_UNISON as f32) as usize;
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *unison_spread = (*unison_spread + dir).clamp(0.0, 100.0);
+                    return;
+                }
+                current_idx += 1;
                 if current_idx == app.param_idx {
                     *pitch_env_amount = (*pitch_env_amount + dir * 10.0).max(0.0);
                     return;
@@ -446,7 +1284,11 @@ fn change_module_param(app: &mut App, dir: f32) {
                 }
                 current_idx += 1;
             }
-            ModuleConfig::Filter { cutoff, resonance } => {
+            ModuleConfig::Filter {
+                cutoff,
+                resonance,
+                velocity_amount,
+            } => {
                 if current_idx == app.param_idx {
                     *cutoff = (*cutoff + dir * 100.0).clamp(20.0, 20000.0);
                     return;
@@ -457,6 +1299,11 @@ fn change_module_param(app: &mut App, dir: f32) {
                     return;
                 }
                 current_idx += 1;
+                if current_idx == app.param_idx {
+                    *velocity_amount = (*velocity_amount + dir * 0.05).clamp(0.0, 1.0);
+                    return;
+                }
+                current_idx += 1;
             }
             ModuleConfig::Adsr {
                 attack,
@@ -492,6 +1339,58 @@ fn change_module_param(app: &mut App, dir: f32) {
                 }
                 current_idx += 1;
             }
+            ModuleConfig::Lfo {
+                waveform,
+                target,
+                rate,
+                sync_rows,
+                depth,
+                retrigger,
+            } => {
+                if current_idx == app.param_idx {
+                    *waveform = if dir > 0.0 {
+                        waveform.next()
+                    } else {
+                        waveform.prev()
+                    };
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *target = if dir > 0.0 {
+                        target.next()
+                    } else {
+                        target.prev()
+                    };
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *rate = (*rate + dir * 0.1).clamp(0.1, 20.0);
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    // Off, then 1, 2, 4 ... rows per cycle
+                    *sync_rows = if dir > 0.0 {
+                        (*sync_rows * 2).clamp(1, MAX_LFO_SYNC_ROWS)
+                    } else {
+                        *sync_rows / 2
+                    };
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *depth = (*depth + dir * 0.01).clamp(0.0, 1.0);
+                    return;
+                }
+                current_idx += 1;
+                if current_idx == app.param_idx {
+                    *retrigger = !*retrigger;
+                    return;
+                }
+                current_idx += 1;
+            }
         }
     }
 }
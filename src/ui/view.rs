@@ -1,6 +1,8 @@
-use super::app::{App, InstrumentFocus, View};
-use crate::core::state::PlayMode;
-use crate::core::{ModuleConfig, NUM_CHANNELS, ROWS_PER_PATTERN, SharedState};
+use super::app::{App, InstrumentFocus, PatternColumn, View};
+use crate::core::groove::MAX_GROOVE_OFFSET;
+use crate::core::instrument::LfoTarget;
+use crate::core::state::{PatternLaunch, PlayMode};
+use crate::core::{InsertConfig, ModuleConfig, NOTE_CUT, NOTE_OFF, SharedState};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
@@ -23,8 +25,8 @@ pub fn ui(f: &mut Frame, app: &mut App) {
         )
         .split(f.area());
 
-    let state_arc = app.state.clone();
-    let state = state_arc.lock().unwrap();
+    let state_rc = app.state.clone();
+    let state = state_rc.borrow();
 
     // Header
     let status = if state.is_playing {
@@ -35,11 +37,20 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     let view_str = match app.current_view {
         View::Pattern => "PATTERN",
         View::Instrument => "INSTRUMENT",
+        View::Order => "ORDER",
+        View::Mixer => "MIXER",
+        View::Strip => "STRIP",
+        View::Groove => "GROOVE",
     };
 
     let inst_text = format!("{:02X}", app.current_instrument_idx);
     let step_text = format!("{}", app.edit_step);
     let pattern_text = format!("{:02X}", state.current_pattern);
+    let queued_text = match state.queued_pattern {
+        Some(pattern) => format!(" > {:02X}", pattern),
+        None => String::new(),
+    };
+    let order_text = format!("{:02X}/{:02X}", state.current_order, state.order.len());
     let mode_text = match state.play_mode {
         PlayMode::Pattern => "PAT",
         PlayMode::Song => "SONG",
@@ -70,6 +81,19 @@ pub fn ui(f: &mut Frame, app: &mut App) {
                 .fg(Color::Yellow)
                 .add_modifier(Modifier::BOLD),
         ),
+        Span::styled(
+            queued_text,
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" | Pos: "),
+        Span::styled(
+            order_text,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ),
         Span::raw(" | Mode: "),
         Span::styled(
             mode_text,
@@ -90,10 +114,18 @@ pub fn ui(f: &mut Frame, app: &mut App) {
     match app.current_view {
         View::Pattern => draw_pattern_view(f, chunks[1], &state, app),
         View::Instrument => draw_instrument_view(f, chunks[1], &state, app),
+        View::Order => draw_order_view(f, chunks[1], &state, app),
+        View::Mixer => draw_mixer_view(f, chunks[1], &state, app),
+        View::Strip => draw_strip_view(f, chunks[1], &state, app),
+        View::Groove => draw_groove_view(f, chunks[1], &state, app),
     }
 
-    // Status Bar
-    let status_bar = Paragraph::new(app.status_message.as_str());
+    // Status Bar, or the tempo prompt while it is open
+    let status_text = match &app.bpm_entry {
+        Some(entry) => format!("BPM: {}_  (Enter: Set, Esc: Cancel)", entry),
+        None => app.status_message.clone(),
+    };
+    let status_bar = Paragraph::new(status_text);
     f.render_widget(status_bar, chunks[2]);
 
     // File Dialog (Overlay)
@@ -113,32 +145,87 @@ fn draw_help_dialog(f: &mut Frame, app: &mut App) {
 
     let help_text = vec![
         "--- General ---",
-        "Tab: Switch View (Pattern/Instrument)",
+        "Tab: Switch View (Pattern/Instrument/Order/Mixer/Strip/Groove)",
         "Space: Play/Stop",
         "Shift+Space: Play from Cursor",
         "F9: Load Project",
         "F10: Save New Project",
         "F11: Save Project",
-        "F12: Render to WAV",
+        "F12: Render to WAV (Shift+F12: with Mute/Solo)",
         "q: Quit",
         "",
         "--- Pattern View ---",
-        "Arrows: Move Cursor",
+        "Arrows: Move Cursor (Note/Instrument/Volume/Effect columns)",
         "z,s,x...: Play Notes (Piano Layout)",
-        "Delete/Backspace/.: Clear Note",
+        "= / ^: Note Off (===) / Note Cut (^^^)",
+        "0-9,a-f: Set Instrument / Volume 00-40 / Effect (hex columns)",
+        "Effects: 0xy Arpeggio, 1xx/2xx Slide Up/Down,",
+        "         3xx Tone Portamento, 4xy Vibrato, 8xx Pan,",
+        "         E9x Retrigger, ECx Note Cut, EDx Note Delay,",
+        "         Bxx Position Jump, Dxx Pattern Break,",
+        "         Fxx Ticks/Row (<20) or BPM (>=20)",
+        "Delete/Backspace/.: Clear Note/Instrument/Volume/Effect",
         "F1/F2: Octave Down/Up",
         "F3/F4: Edit Step Down/Up",
-        "F5/F6: Prev/Next Pattern",
+        "F5/F6: Prev/Next Pattern (while playing: switch or queue)",
+        "K: Pattern Launch (Immediate/Pattern End/Every 4-16 Rows)",
         "F7/F8: BPM Down/Up",
+        "Shift+Up/Down: Select Rows, Esc: Clear Selection",
+        "R: Play Current Row, P: Play Selected Rows",
+        "W: Loop Selected Rows (again without selection: off)",
+        "T: Type BPM (e.g. 127.5)",
         "p: Toggle Play Mode (Pattern/Song)",
         "n: Clone Current Pattern",
+        "[ / ]: Pattern Length -1 / +1 Row",
+        "{ / }: Halve / Double Pattern Length",
+        "< / >: Remove / Add Channel (4-64)",
+        "M / S: Mute / Solo Channel, U: Clear Mute/Solo",
+        "V: Cycle Voice Mode (Retrigger/Legato/Glide/Poly)",
+        "L: Poly Voice Limit (1-16), O: Steal Oldest/Quietest",
+        "( / ): Pan Channel Left / Right",
         "x: Delete Current Pattern",
         "",
+        "--- Order View ---",
+        "Up/Down: Select Song Position",
+        "Shift+Up/Down: Move Entry",
+        "Left/Right, -/+: Change Pattern at Position",
+        "Insert/i: Duplicate Entry",
+        "Delete/Backspace: Remove Entry",
+        "Enter: Edit Pattern at Position",
+        "[ / ]: Song Loop Start / End, l: Loop Off",
+        "",
+        "--- Mixer View ---",
+        "Up/Down: Select Channel / Send Return / Master",
+        "Left/Right, -/+: Fader -/+ 1 dB, 0: Reset to 0 dB",
+        "( / ): Pan Left / Right",
+        "[ / ], { / }: Reverb / Delay Send",
+        "Reverb Row: [ / ] Room Size, { / } Damping",
+        "Delay Row: [ / ] Left Rows, < / > Right Rows, { / } Feedback",
+        "m / s / u: Mute / Solo / Clear",
+        "Enter: Open Channel Strip",
+        "",
+        "--- Channel Strip View ---",
+        "Left/Right: Select Channel",
+        "Up/Down: Select Insert Parameter",
+        "+/-: Change Parameter Value",
+        "1-4: Add Filter / Distortion / Chorus / Compressor",
+        "x/Delete: Remove Insert",
+        "[ / ]: Move Insert Earlier / Later",
+        "",
+        "--- Groove View ---",
+        "Up/Down: Select Swing / Row of the Cycle",
+        "Left/Right, -/+: Offset -/+ 5% of a Row, 0: Reset",
+        "< / >: Cycle Length 2/4/8/16 Rows",
+        "g: Toggle Own Groove for the Current Pattern",
+        "",
         "--- Instrument View ---",
         "Arrows: Navigate List/Params",
         "Enter/Right: Edit Params",
         "Esc/Left: Back to List",
         "+/-: Change Parameter Value",
+        "o: Add Oscillator (layers with the others)",
+        "l: Add LFO (Pitch/Cutoff/Gain, Hz or synced to rows)",
+        "x/Delete: Remove Selected Oscillator or LFO",
         "0-9: Quick Select Instrument",
     ];
 
@@ -203,28 +290,61 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
 }
 
 fn get_channel_color(channel: usize) -> Color {
-    match channel {
-        0..=3 => Color::Yellow, // Rhythm / Bass
-        4..=7 => Color::Cyan,   // Melodic / Synths
-        8..=11 => Color::Green, // Atmos / FX
-        _ => Color::Magenta,    // Misc
+    // Colors repeat every 16 channels
+    match (channel / 4) % 4 {
+        0 => Color::Yellow,  // Rhythm / Bass
+        1 => Color::Cyan,    // Melodic / Synths
+        2 => Color::Green,   // Atmos / FX
+        _ => Color::Magenta, // Misc
     }
 }
 
-fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &App) {
+// Whether `pos` lies in an optional first..=last range.
+fn in_range(range: Option<(usize, usize)>, pos: usize) -> bool {
+    range.is_some_and(|(first, last)| (first..=last).contains(&pos))
+}
+
+fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
     let play_pos_style = Style::default().bg(Color::DarkGray);
     let cursor_play_style = Style::default()
         .bg(Color::DarkGray)
         .add_modifier(Modifier::REVERSED);
 
-    let mut header_cells = Vec::with_capacity(NUM_CHANNELS + 1);
+    // Scroll horizontally so the cursor channel stays visible
+    // Borders (2) + row number column (4 + 1 spacing), then 13 + 1 per channel
+    let visible_channels =
+        ((area.width as usize).saturating_sub(7) / 14).clamp(1, state.num_channels);
+    if app.cursor_channel < app.channel_offset {
+        app.channel_offset = app.cursor_channel;
+    } else if app.cursor_channel >= app.channel_offset + visible_channels {
+        app.channel_offset = app.cursor_channel + 1 - visible_channels;
+    }
+    app.channel_offset = app
+        .channel_offset
+        .min(state.num_channels - visible_channels);
+    let first_channel = app.channel_offset;
+    let last_channel = first_channel + visible_channels;
+
+    let mut header_cells = Vec::with_capacity(visible_channels + 1);
     header_cells.push(Cell::from("Row").style(Style::default().fg(Color::White)));
 
-    for i in 0..NUM_CHANNELS {
-        let col_color = get_channel_color(i);
-        // Header with colored background and black text for contrast
+    for i in first_channel..last_channel {
+        let settings = &state.channel_settings[i];
+        let flags = match (settings.muted, settings.solo) {
+            (true, true) => " M S",
+            (true, false) => " M",
+            (false, true) => " S",
+            (false, false) => "",
+        };
+        let label = format!("{:02X} {}{}", i, settings.voice_mode.short_name(), flags);
+        // Header with colored background and black text for contrast; silent channels are grayed
+        let col_color = if state.is_channel_audible(i) {
+            get_channel_color(i)
+        } else {
+            Color::DarkGray
+        };
         header_cells.push(
-            Cell::from(format!("{:X}", i)).style(
+            Cell::from(label).style(
                 Style::default()
                     .bg(col_color)
                     .fg(Color::Black)
@@ -246,13 +366,30 @@ fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &App)
     let half_height = inner_height / 2;
     let start_row = center_row.saturating_sub(half_height);
 
-    let start_row = if start_row + inner_height > ROWS_PER_PATTERN {
-        ROWS_PER_PATTERN.saturating_sub(inner_height)
+    let pattern_idx = state.current_pattern;
+    let pattern_len = state
+        .patterns
+        .get(pattern_idx)
+        .map_or(0, |pattern| pattern.len());
+
+    let start_row = if start_row + inner_height > pattern_len {
+        pattern_len.saturating_sub(inner_height)
     } else {
         start_row
     };
 
-    let pattern_idx = state.current_pattern;
+    // Selected rows are highlighted in the row column, rows in the loop region are yellow
+    let selection = app.selected_rows();
+    let row_number_style = |row: usize| {
+        if in_range(selection, row) {
+            Style::default().bg(Color::Blue).fg(Color::White)
+        } else if in_range(state.loop_rows, row) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }
+    };
+
     let rows = if pattern_idx < state.patterns.len() {
         state.patterns[pattern_idx]
             .rows
@@ -261,16 +398,23 @@ fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &App)
             .skip(start_row)
             .take(inner_height)
             .map(|(i, row_data)| {
-                let mut cells = Vec::with_capacity(NUM_CHANNELS + 1);
-                cells.push(
-                    Cell::from(format!("{:02X}", i)).style(Style::default().fg(Color::DarkGray)),
-                );
+                let mut cells = Vec::with_capacity(visible_channels + 1);
+                cells.push(Cell::from(format!("{:02X}", i)).style(row_number_style(i)));
 
-                for (ch_idx, note) in row_data.iter().enumerate() {
+                for (ch_idx, note) in row_data
+                    .iter()
+                    .enumerate()
+                    .take(last_channel)
+                    .skip(first_channel)
+                {
                     let col_color = get_channel_color(ch_idx);
 
                     let note_str = if note.key == 0 {
                         "---".to_string()
+                    } else if note.key == NOTE_OFF {
+                        "===".to_string()
+                    } else if note.key == NOTE_CUT {
+                        "^^^".to_string()
                     } else {
                         let notes = [
                             "C-", "C#", "D-", "D#", "E-", "F-", "F#", "G-", "G#", "A-", "A#", "B-",
@@ -280,20 +424,54 @@ fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &App)
                         format!("{}{}", notes[note_idx], octave)
                     };
 
-                    let cell = Cell::from(note_str);
+                    let inst_str = match note.instrument {
+                        Some(inst) => format!("{:02X}", inst),
+                        None => "--".to_string(),
+                    };
+
+                    let vol_str = match note.volume {
+                        Some(vol) => format!("{:02X}", vol),
+                        None => "--".to_string(),
+                    };
+
+                    let (fx_str, fx_param_str) = if note.has_effect() {
+                        (
+                            format!("{:X}", note.effect),
+                            format!("{:02X}", note.effect_param),
+                        )
+                    } else {
+                        ("-".to_string(), "--".to_string())
+                    };
 
-                    // Determine style
-                    if i == app.cursor_row && ch_idx == app.cursor_channel {
-                        if i == state.current_row {
-                            cells.push(cell.style(cursor_play_style));
+                    // Normal cell: Use column color for text
+                    let normal_style = Style::default().fg(col_color);
+                    let column_style = |column: PatternColumn| {
+                        if i == app.cursor_row
+                            && ch_idx == app.cursor_channel
+                            && column == app.cursor_column
+                        {
+                            if i == state.current_row {
+                                cursor_play_style
+                            } else {
+                                // Cursor: Reverse video, keep color tint if possible or just standard reverse
+                                Style::default().bg(col_color).fg(Color::Black)
+                            }
                         } else {
-                            // Cursor: Reverse video, keep color tint if possible or just standard reverse
-                            cells.push(cell.style(Style::default().bg(col_color).fg(Color::Black)));
+                            normal_style
                         }
-                    } else {
-                        // Normal cell: Use column color for text
-                        cells.push(cell.style(Style::default().fg(col_color)));
-                    }
+                    };
+
+                    let cell = Cell::from(Line::from(vec![
+                        Span::styled(note_str, column_style(PatternColumn::Note)),
+                        Span::styled(" ", normal_style),
+                        Span::styled(inst_str, column_style(PatternColumn::Instrument)),
+                        Span::styled(" ", normal_style),
+                        Span::styled(vol_str, column_style(PatternColumn::Volume)),
+                        Span::styled(" ", normal_style),
+                        Span::styled(fx_str, column_style(PatternColumn::Effect)),
+                        Span::styled(fx_param_str, column_style(PatternColumn::EffectParam)),
+                    ]));
+                    cells.push(cell);
                 }
 
                 let row_style = if i == state.current_row {
@@ -309,21 +487,403 @@ fn draw_pattern_view(f: &mut Frame, area: Rect, state: &SharedState, app: &App)
         Vec::new()
     };
 
-    let mut widths = Vec::with_capacity(NUM_CHANNELS + 1);
+    let mut widths = Vec::with_capacity(visible_channels + 1);
     widths.push(Constraint::Length(4));
-    for _ in 0..NUM_CHANNELS {
-        widths.push(Constraint::Length(4));
+    for _ in first_channel..last_channel {
+        widths.push(Constraint::Length(13));
     }
 
+    let loop_info = match state.loop_rows {
+        Some((first, last)) => format!(" | Loop {:02X}-{:02X}", first, last),
+        None => String::new(),
+    };
+    let launch_info = match state.pattern_launch {
+        PatternLaunch::Immediate => String::new(),
+        launch => format!(" | Launch: {}", launch.name()),
+    };
     let t = Table::new(rows, widths).header(header).block(
-        Block::default()
-            .borders(Borders::ALL)
-            .title(format!("Pattern {:02X}", pattern_idx)),
+        Block::default().borders(Borders::ALL).title(format!(
+            "Pattern {:02X} ({} rows) | Channels {:02X}-{:02X} of {}{}{}",
+            pattern_idx,
+            pattern_len,
+            first_channel,
+            last_channel - 1,
+            state.num_channels,
+            loop_info,
+            launch_info
+        )),
     );
 
     f.render_widget(t, area);
 }
 
+fn draw_order_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
+    let items: Vec<ListItem> = state
+        .order
+        .iter()
+        .enumerate()
+        .map(|(pos, &pattern_idx)| {
+            let is_play_pos =
+                state.is_playing && state.play_mode == PlayMode::Song && pos == state.current_order;
+            let marker = if is_play_pos { ">" } else { " " };
+            let loop_marker = if in_range(state.loop_orders, pos) {
+                "|"
+            } else {
+                " "
+            };
+            let rows = state
+                .patterns
+                .get(pattern_idx)
+                .map_or(0, |pattern| pattern.len());
+
+            let style = if pos == app.order_cursor {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else if is_play_pos {
+                Style::default().bg(Color::DarkGray)
+            } else {
+                Style::default()
+            };
+            ListItem::new(format!(
+                "{}{} {:02X} - Pattern {:02X} ({} rows)",
+                marker, loop_marker, pos, pattern_idx, rows
+            ))
+            .style(style)
+        })
+        .collect();
+
+    let loop_info = match state.loop_orders {
+        Some((first, last)) => format!(" | Loop {:02X}-{:02X}", first, last),
+        None => String::new(),
+    };
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(format!(
+        "Song Order ({} patterns){}",
+        state.patterns.len(),
+        loop_info
+    )));
+
+    f.render_stateful_widget(list, area, &mut app.order_list_state);
+}
+
+fn format_db(gain: f32) -> String {
+    if gain <= 0.0 {
+        "-inf dB".to_string()
+    } else {
+        format!("{:+.1} dB", 20.0 * gain.log10())
+    }
+}
+
+fn format_send(amount: f32) -> String {
+    if amount <= 0.0 {
+        "-".to_string()
+    } else {
+        format!("{}", (amount * 100.0).round() as u32)
+    }
+}
+
+fn format_pan(pan: f32) -> String {
+    let percent = (pan.abs() * 100.0).round() as u32;
+    if percent == 0 {
+        "C".to_string()
+    } else if pan < 0.0 {
+        format!("L{}", percent)
+    } else {
+        format!("R{}", percent)
+    }
+}
+
+// Level meter over -48..0 dB, green / yellow / red towards full scale.
+fn meter_line(peak: f32, width: usize) -> Line<'static> {
+    const METER_RANGE_DB: f32 = 48.0;
+    let db = if peak > 0.0 {
+        20.0 * peak.log10()
+    } else {
+        -METER_RANGE_DB
+    };
+    let filled = (((db + METER_RANGE_DB) / METER_RANGE_DB).clamp(0.0, 1.0) * width as f32) as usize;
+    let color = if db >= -3.0 {
+        Color::Red
+    } else if db >= -12.0 {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+    Line::from(vec![
+        Span::styled("|".repeat(filled), Style::default().fg(color)),
+        Span::styled(
+            ".".repeat(width - filled),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ])
+}
+
+fn draw_mixer_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
+    const METER_WIDTH: usize = 32;
+
+    let header = Row::new(vec![
+        "Ch", "Level", "Peak", "Fader", "Pan", "Rev", "Dly", "Mode", "M/S",
+    ])
+    .style(Style::default().fg(Color::White))
+    .bottom_margin(1);
+
+    let mut rows = Vec::with_capacity(state.num_channels + 3);
+    for (i, settings) in state
+        .channel_settings
+        .iter()
+        .take(state.num_channels)
+        .enumerate()
+    {
+        let peak = state.channel_peaks.get(i).copied().unwrap_or(0.0);
+        let flags = format!(
+            "{}{}",
+            if settings.muted { "M" } else { "-" },
+            if settings.solo { "S" } else { "-" }
+        );
+        let color = if state.is_channel_audible(i) {
+            get_channel_color(i)
+        } else {
+            Color::DarkGray
+        };
+        rows.push(Row::new(vec![
+            Cell::from(format!("{:02X}", i)).style(Style::default().fg(color)),
+            Cell::from(meter_line(peak, METER_WIDTH)),
+            Cell::from(format_db(peak)),
+            Cell::from(format_db(settings.volume)),
+            Cell::from(format_pan(settings.pan)),
+            Cell::from(format_send(settings.reverb_send)),
+            Cell::from(format_send(settings.delay_send)),
+            Cell::from(settings.voice_mode.short_name()),
+            Cell::from(flags),
+        ]));
+    }
+
+    let bus_style = Style::default().add_modifier(Modifier::BOLD);
+    for (label, volume) in [
+        ("Rev", state.sends.reverb_return),
+        ("Dly", state.sends.delay_return),
+    ] {
+        rows.push(Row::new(vec![
+            Cell::from(label).style(bus_style.fg(Color::Cyan)),
+            Cell::from(""),
+            Cell::from(""),
+            Cell::from(format_db(volume)),
+        ]));
+    }
+    rows.push(Row::new(vec![
+        Cell::from("Mst").style(bus_style),
+        Cell::from(meter_line(state.master_peak, METER_WIDTH)),
+        Cell::from(format_db(state.master_peak)),
+        Cell::from(format_db(state.master_volume)),
+    ]));
+
+    let widths = [
+        Constraint::Length(3),
+        Constraint::Length(METER_WIDTH as u16),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(4),
+        Constraint::Length(4),
+        Constraint::Length(4),
+        Constraint::Length(4),
+        Constraint::Length(3),
+    ];
+
+    let sends = &state.sends;
+    let send_info = format!(
+        " Reverb: room {:.2} damping {:.2} | Delay: L {} R {} rows, feedback {:.2} ",
+        sends.reverb_room_size,
+        sends.reverb_damping,
+        sends.delay_rows_left,
+        sends.delay_rows_right,
+        sends.delay_feedback
+    );
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Mixer (master bus is limited to -1 dB)")
+                .title_bottom(send_info),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(table, area, &mut app.mixer_table_state);
+}
+
+fn draw_strip_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)].as_ref())
+        .split(area);
+
+    let ch = app.cursor_channel.min(state.num_channels - 1);
+    let settings = &state.channel_settings[ch];
+
+    // Left: Channel overview, edited in the pattern and mixer views
+    let flags = match (settings.muted, settings.solo) {
+        (true, true) => "Muted, Solo",
+        (true, false) => "Muted",
+        (false, true) => "Solo",
+        (false, false) => "-",
+    };
+    let mut lines = vec![
+        Line::from(format!("Voice Mode: {:?}", settings.voice_mode)),
+        Line::from(format!("Voices:     {}", settings.voice_limit)),
+        Line::from(format!("Fader:      {}", format_db(settings.volume))),
+        Line::from(format!("Pan:        {}", format_pan(settings.pan))),
+        Line::from(format!("Reverb:     {}", format_send(settings.reverb_send))),
+        Line::from(format!("Delay:      {}", format_send(settings.delay_send))),
+        Line::from(format!("M/S:        {}", flags)),
+        Line::from(""),
+        Line::from("Signal Chain:"),
+        Line::from("  Voices > Pan"),
+    ];
+    for insert in &settings.inserts {
+        lines.push(Line::from(format!("  > {}", insert.name())));
+    }
+    lines.push(Line::from("  > Fader > Sends"));
+    let strip_block = Block::default().borders(Borders::ALL).title(Span::styled(
+        format!("Channel {:02X}", ch),
+        Style::default().fg(get_channel_color(ch)),
+    ));
+    let strip = Paragraph::new(lines).block(strip_block);
+    f.render_widget(strip, chunks[0]);
+
+    // Right: Insert chain (Flattened list of all insert params)
+    let mut params = Vec::new();
+    for (idx, insert) in settings.inserts.iter().enumerate() {
+        let prefix = format!("I{}: ", idx);
+        match insert {
+            InsertConfig::Filter { mode, cutoff, q } => {
+                params.push((format!("{}Filt Mode", prefix), format!("{:?}", mode)));
+                params.push((format!("{}Filt Cut", prefix), format!("{:.0} Hz", cutoff)));
+                params.push((format!("{}Filt Q", prefix), format!("{:.1}", q)));
+            }
+            InsertConfig::Distortion { mode, drive, mix } => {
+                params.push((format!("{}Dist Mode", prefix), format!("{:?}", mode)));
+                params.push((format!("{}Dist Drive", prefix), format!("{:.1}", drive)));
+                params.push((format!("{}Dist Mix", prefix), format!("{:.2}", mix)));
+            }
+            InsertConfig::Chorus {
+                depth,
+                feedback,
+                mix,
+            } => {
+                params.push((format!("{}Chor Depth", prefix), format!("{:.1} ms", depth)));
+                params.push((format!("{}Chor Fdbk", prefix), format!("{:.2}", feedback)));
+                params.push((format!("{}Chor Mix", prefix), format!("{:.2}", mix)));
+            }
+            InsertConfig::Compressor {
+                threshold,
+                ratio,
+                attack,
+                release,
+                makeup,
+            } => {
+                params.push((
+                    format!("{}Comp Thr", prefix),
+                    format!("{:.0} dB", threshold),
+                ));
+                params.push((format!("{}Comp Ratio", prefix), format!("{:.1}:1", ratio)));
+                params.push((format!("{}Comp Att", prefix), format!("{:.0} ms", attack)));
+                params.push((format!("{}Comp Rel", prefix), format!("{:.0} ms", release)));
+                params.push((format!("{}Comp Gain", prefix), format!("{:.0} dB", makeup)));
+            }
+        }
+    }
+
+    let rows = params
+        .iter()
+        .map(|(name, val)| Row::new(vec![Cell::from(name.as_str()), Cell::from(val.as_str())]));
+
+    let title = if params.is_empty() {
+        "Inserts (empty, 1-4 adds an effect)"
+    } else {
+        "Inserts (before the fader)"
+    };
+    let table = Table::new(rows, [Constraint::Length(20), Constraint::Length(15)])
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(table, chunks[1], &mut app.strip_table_state);
+}
+
+fn format_offset(offset: f32) -> String {
+    format!("{:+}%", (offset * 100.0).round() as i32)
+}
+
+// Where a row lands between a half row early and a half row late, `|` is on time.
+fn groove_line(offset: f32, width: usize) -> Line<'static> {
+    let centre = width / 2;
+    let pos = (centre as f32 + (offset / MAX_GROOVE_OFFSET) * centre as f32).round() as usize;
+    let lane = |range: std::ops::Range<usize>| -> String {
+        range.map(|i| if i == centre { '|' } else { '.' }).collect()
+    };
+    Line::from(vec![
+        Span::styled(lane(0..pos), Style::default().fg(Color::DarkGray)),
+        Span::styled("o", Style::default().fg(Color::Yellow)),
+        Span::styled(lane(pos + 1..width), Style::default().fg(Color::DarkGray)),
+    ])
+}
+
+fn draw_groove_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
+    const LANE_WIDTH: usize = 41;
+
+    let pattern_idx = state.current_pattern;
+    let has_own_groove = state.patterns[pattern_idx].groove.is_some();
+    let groove = state.groove(pattern_idx);
+
+    let header = Row::new(vec!["Row", "Offset", "Timing"])
+        .style(Style::default().fg(Color::White))
+        .bottom_margin(1);
+
+    let mut rows = vec![Row::new(vec![
+        Cell::from("Swing").style(Style::default().add_modifier(Modifier::BOLD)),
+        Cell::from(format_offset(groove.swing)),
+        Cell::from("Every second row plays late"),
+    ])];
+    for (i, offset) in groove.offsets.iter().enumerate() {
+        rows.push(Row::new(vec![
+            Cell::from(format!("{:02}", i)),
+            Cell::from(format_offset(*offset)),
+            Cell::from(groove_line(groove.row_offset(i), LANE_WIDTH)),
+        ]));
+    }
+
+    let widths = [
+        Constraint::Length(5),
+        Constraint::Length(7),
+        Constraint::Length(LANE_WIDTH as u16),
+    ];
+
+    let title = if has_own_groove {
+        format!(
+            "Groove of Pattern {:02X} ({} row cycle)",
+            pattern_idx,
+            groove.offsets.len()
+        )
+    } else {
+        format!("Project Groove ({} row cycle)", groove.offsets.len())
+    };
+    let owner_info = if has_own_groove {
+        " g: Use Project Groove ".to_string()
+    } else {
+        format!(" g: Own Groove for Pattern {:02X} ", pattern_idx)
+    };
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .title_bottom(owner_info),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(table, area, &mut app.groove_table_state);
+}
+
 fn draw_instrument_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
@@ -361,11 +921,26 @@ fn draw_instrument_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mu
         match module {
             ModuleConfig::Oscillator {
                 waveform,
+                pitch_offset,
+                detune,
                 pitch_env_amount,
                 pitch_env_decay,
-                ..
+                level,
+                unison,
+                unison_spread,
             } => {
                 params.push((format!("{}Osc Wave", prefix), format!("{:?}", waveform)));
+                params.push((
+                    format!("{}Osc Pitch", prefix),
+                    format!("{:+.0} st", pitch_offset),
+                ));
+                params.push((format!("{}Osc Fine", prefix), format!("{:+.0} ct", detune)));
+                params.push((format!("{}Osc Level", prefix), format!("{:.2}", level)));
+                params.push((format!("{}Osc Voices", prefix), format!("{}", unison)));
+                params.push((
+                    format!("{}Osc Spread", prefix),
+                    format!("{:.0} ct", unison_spread),
+                ));
                 params.push((
                     format!("{}PE Amt", prefix),
                     format!("{:.0} Hz", pitch_env_amount),
@@ -375,9 +950,17 @@ fn draw_instrument_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mu
                     format!("{:.3} s", pitch_env_decay),
                 ));
             }
-            ModuleConfig::Filter { cutoff, resonance } => {
+            ModuleConfig::Filter {
+                cutoff,
+                resonance,
+                velocity_amount,
+            } => {
                 params.push((format!("{}Filt Cut", prefix), format!("{:.0} Hz", cutoff)));
                 params.push((format!("{}Filt Res", prefix), format!("{:.2}", resonance)));
+                params.push((
+                    format!("{}Filt Vel", prefix),
+                    format!("{:.2}", velocity_amount),
+                ));
             }
             ModuleConfig::Adsr {
                 attack,
@@ -393,6 +976,33 @@ fn draw_instrument_view(f: &mut Frame, area: Rect, state: &SharedState, app: &mu
             ModuleConfig::Gain { level } => {
                 params.push((format!("{}Gain Lvl", prefix), format!("{:.2}", level)));
             }
+            ModuleConfig::Lfo {
+                waveform,
+                target,
+                rate,
+                sync_rows,
+                depth,
+                retrigger,
+            } => {
+                let sync = match sync_rows {
+                    0 => "Off".to_string(),
+                    rows => format!("{} rows", rows),
+                };
+                let depth_text = match target {
+                    LfoTarget::Pitch => format!("{:.2} st", depth * 12.0),
+                    LfoTarget::Cutoff => format!("{:.2} oct", depth * 4.0),
+                    LfoTarget::Gain => format!("{:.0}%", depth * 100.0),
+                };
+                params.push((format!("{}LFO Wave", prefix), format!("{:?}", waveform)));
+                params.push((format!("{}LFO Target", prefix), format!("{:?}", target)));
+                params.push((format!("{}LFO Rate", prefix), format!("{:.1} Hz", rate)));
+                params.push((format!("{}LFO Sync", prefix), sync));
+                params.push((format!("{}LFO Depth", prefix), depth_text));
+                params.push((
+                    format!("{}LFO Retrig", prefix),
+                    if *retrigger { "On" } else { "Off" }.to_string(),
+                ));
+            }
         }
     }
 